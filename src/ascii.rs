@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_modbus::Slave;
+
+use crate::{
+    errors::ModbusError,
+    pdu::{Request, Response},
+    transport::Transport,
+};
+
+// longitudinal redundancy check, two's complement of the sum of the bytes
+pub fn lrc(data: &[u8]) -> u8 {
+    data.iter()
+        .fold(0u8, |acc, byte| acc.wrapping_add(*byte))
+        .wrapping_neg()
+}
+
+// ':' followed by the slave, the PDU and the LRC in hexadecimal, ended by CRLF
+pub fn encode_frame(slave: u8, pdu: &[u8]) -> Vec<u8> {
+    let data = [&[slave], pdu].concat();
+    let mut frame = vec![b':'];
+    for byte in data.iter().chain([lrc(&data)].iter()) {
+        frame.extend(format!("{byte:02X}").as_bytes());
+    }
+    frame.extend(b"\r\n");
+    frame
+}
+
+// slave and PDU of the content of a frame (between ':' and CRLF), none if it is invalid
+pub fn decode_frame(hex: &[u8]) -> Option<(u8, Vec<u8>)> {
+    let digit = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+    let bytes = hex
+        .chunks(2)
+        .map(|pair| match pair {
+            [high, low] => Some((digit(*high)? << 4) | digit(*low)?),
+            _ => None,
+        })
+        .collect::<Option<Vec<u8>>>()?;
+
+    // slave, function code and LRC at least
+    if bytes.len() < 3 {
+        return None;
+    }
+    let (data, checksum) = bytes.split_at(bytes.len() - 1);
+    if lrc(data) != checksum[0] {
+        return None;
+    }
+    Some((data[0], data[1..].to_vec()))
+}
+
+// content of the next frame, the frames interrupted for longer than the inter-character timeout are dropped
+pub(crate) async fn read_frame<T>(
+    port: &mut T,
+    inter_char_timeout: Option<Duration>,
+) -> std::io::Result<Vec<u8>>
+where
+    T: AsyncRead + Unpin,
+{
+    let mut frame = Vec::new();
+    let mut started = false;
+    loop {
+        let byte = match (started, inter_char_timeout) {
+            (true, Some(timeout)) => match tokio::time::timeout(timeout, port.read_u8()).await {
+                Ok(byte) => byte?,
+                Err(_) => {
+                    warn!("ASCII frame interrupted, dropping it");
+                    started = false;
+                    continue;
+                }
+            },
+            _ => port.read_u8().await?,
+        };
+        match byte {
+            b':' => {
+                started = true;
+                frame.clear();
+            }
+            b'\n' if started && frame.last() == Some(&b'\r') => {
+                frame.pop();
+                return Ok(frame);
+            }
+            _ if started => frame.push(byte),
+            _ => {}
+        }
+    }
+}
+
+// Modbus ASCII on a serial line
+#[derive(Debug)]
+pub struct AsciiTransport<T> {
+    port: T,
+    slave: Slave,
+    inter_char_timeout: Option<Duration>,
+}
+
+impl<T> AsciiTransport<T> {
+    pub fn new(port: T, slave: Slave, inter_char_timeout: Option<Duration>) -> Self {
+        AsciiTransport {
+            port,
+            slave,
+            inter_char_timeout,
+        }
+    }
+}
+
+#[async_trait]
+impl<T> Transport for AsciiTransport<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + std::fmt::Debug,
+{
+    async fn call(&mut self, request: Request) -> Result<Response, ModbusError> {
+        let frame = encode_frame(self.slave.0, &request.encode());
+        self.port.write_all(&frame).await?;
+
+        loop {
+            let frame = read_frame(&mut self.port, self.inter_char_timeout).await?;
+            match decode_frame(&frame) {
+                Some((slave, pdu)) if slave == self.slave.0 => {
+                    return Response::decode(&request, &pdu)
+                }
+                Some((slave, _)) => debug!("Received a frame of slave {slave}, dropping it"),
+                None => warn!("Received an invalid ASCII frame, dropping it"),
+            }
+        }
+    }
+
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave = slave;
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use crate::types::{ModBusRegisters, RegisterValue};
+
+// change of a numeric value below which it is considered unchanged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Deadband {
+    Absolute(f64),
+    Percent(f64), // of the last value reported
+}
+
+impl Deadband {
+    fn exceeded(&self, last: f64, val: f64) -> bool {
+        let delta = (val - last).abs();
+        match self {
+            Deadband::Absolute(band) => delta > *band,
+            Deadband::Percent(band) => delta > last.abs() * band / 100.0,
+        }
+    }
+}
+
+// keeps the last value reported for each register to report only the changes,
+// the registers are identified by their table and name
+#[derive(Debug, Clone, Default)]
+pub struct ChangeFilter {
+    deadbands: HashMap<(ModBusRegisters, String), Deadband>,
+    last: HashMap<(ModBusRegisters, String), RegisterValue>,
+}
+
+impl ChangeFilter {
+    pub fn new() -> Self {
+        ChangeFilter::default()
+    }
+
+    // ignored for the values that are not numbers
+    pub fn with_deadband(
+        mut self,
+        source: ModBusRegisters,
+        name: &str,
+        deadband: Deadband,
+    ) -> Self {
+        self.deadbands.insert((source, name.to_string()), deadband);
+        self
+    }
+
+    // whether the value must be reported, it becomes the reference of the next changes if so
+    pub fn update(&mut self, source: &ModBusRegisters, name: &str, val: &RegisterValue) -> bool {
+        let key = (source.clone(), name.to_string());
+        let changed = match self.last.get(&key) {
+            None => true,
+            Some(last) => match (self.deadbands.get(&key), last.as_f64(), val.as_f64()) {
+                (Some(deadband), Some(last), Some(val)) => deadband.exceeded(last, val),
+                _ => last != val,
+            },
+        };
+        if changed {
+            self.last.insert(key, val.clone());
+        }
+        changed
+    }
+
+    // the next value of the register will be reported
+    pub fn reset(&mut self, source: &ModBusRegisters, name: &str) {
+        self.last.remove(&(source.clone(), name.to_string()));
+    }
+}
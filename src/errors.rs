@@ -11,6 +11,32 @@ custom_error! {pub ModbusError
     ConversionError = "Conversion error",
     DeviceNotConnectedError = "Device is not connected",
     RegisterDoesNotExistError{ name: String } = "Register {name} was not found",
+    UnsupportedTableError = "Operation is not supported on this register table",
+    Timeout = "The device did not respond in time",
+    SerialError {err: tokio_serial::Error} = "Serial port error : {err}",
+    UnexpectedResponseError = "Unexpected response from the device",
+    UnknownLabelError{ label: String } = "Label {label} is not a value of the register",
+    QuantityError = "The number of values doesn't fit in a single request",
+    UnrepresentableValueError{ names: String } = "Registers {names} can't be represented as values",
+}
+
+// reason a single register of a read could not be returned
+custom_error! {pub RegisterError
+    UnknownRegister{ name: String } = "Register {name} was not found",
+    ConversionError{ raw: Vec<u16> } = "Could not convert the registers {raw:?}",
+    Exception{ err: Exception } = "Modbus exception : {err}",
+}
+
+impl From<RegisterError> for ModbusError {
+    fn from(value: RegisterError) -> Self {
+        match value {
+            RegisterError::UnknownRegister { name } => {
+                ModbusError::RegisterDoesNotExistError { name }
+            }
+            RegisterError::ConversionError { .. } => ModbusError::ConversionError,
+            RegisterError::Exception { err } => ModbusError::Exception { err },
+        }
+    }
 }
 
 impl From<Exception> for ModbusError {
@@ -28,8 +54,38 @@ impl From<tokio_modbus::Error> for ModbusError {
         ModbusError::ModbusError { err: value }
     }
 }
+impl From<tokio_serial::Error> for ModbusError {
+    fn from(value: tokio_serial::Error) -> Self {
+        ModbusError::SerialError { err: value }
+    }
+}
 impl From<TryFromSliceError> for ModbusError {
     fn from(value: TryFromSliceError) -> Self {
         ModbusError::TryFromSliceError { err: value }
     }
 }
+
+impl ModbusError {
+    // the connection to the device is lost and needs to be re-established
+    pub fn is_connection_error(&self) -> bool {
+        let is_connection_kind = |kind: std::io::ErrorKind| {
+            matches!(
+                kind,
+                std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::NotConnected
+                    | std::io::ErrorKind::UnexpectedEof
+                    | std::io::ErrorKind::TimedOut
+            )
+        };
+        match self {
+            ModbusError::Timeout => true,
+            ModbusError::IOerror { err } => is_connection_kind(err.kind()),
+            ModbusError::ModbusError {
+                err: tokio_modbus::Error::Transport(err),
+            } => is_connection_kind(err.kind()),
+            _ => false,
+        }
+    }
+}
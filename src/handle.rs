@@ -0,0 +1,252 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    future::Future,
+    pin::Pin,
+};
+
+use tokio::sync::{mpsc, oneshot, watch};
+
+use crate::{
+    errors::ModbusError,
+    identification::{DeviceIdCode, DeviceIdentification},
+    modbus_connexion_async::ModbusConnexionAsync,
+    reconnect::ConnectionState,
+    types::{ModBusRegisters, RegisterResult, RegisterValue},
+    ModbusDeviceAsync,
+};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+type Job = Box<dyn for<'a> FnOnce(&'a mut ModbusDeviceAsync) -> BoxFuture<'a, ()> + Send>;
+
+// the queued requests with the highest priority are sent first, in the order they were queued
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low, // ex : background polling
+    #[default]
+    Normal,
+    High,
+}
+
+struct Queued {
+    priority: Priority,
+    seq: u64,
+    job: Job,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then(other.seq.cmp(&self.seq))
+    }
+}
+
+fn job<F>(f: F) -> Job
+where
+    F: for<'a> FnOnce(&'a mut ModbusDeviceAsync) -> BoxFuture<'a, ()> + Send + 'static,
+{
+    Box::new(f)
+}
+
+// owns the device and runs the requests one at a time, until every handle is dropped
+async fn serve(mut device: ModbusDeviceAsync, mut rx: mpsc::UnboundedReceiver<(Priority, Job)>) {
+    let mut queue = BinaryHeap::new();
+    let mut seq: u64 = 0;
+    loop {
+        if queue.is_empty() {
+            match rx.recv().await {
+                Some((priority, job)) => queue.push(Queued { priority, seq, job }),
+                None => return,
+            }
+            seq += 1;
+        }
+        while let Ok((priority, job)) = rx.try_recv() {
+            queue.push(Queued { priority, seq, job });
+            seq += 1;
+        }
+        if let Some(queued) = queue.pop() {
+            (queued.job)(&mut device).await;
+        }
+    }
+}
+
+// cloneable access to a device shared between tasks, the transactions are sent one at a time
+// a request is cancelled by dropping its future before it is sent
+#[derive(Debug, Clone)]
+pub struct DeviceHandle {
+    queue: mpsc::UnboundedSender<(Priority, Job)>,
+    state: watch::Receiver<ConnectionState>,
+    priority: Priority,
+}
+
+impl DeviceHandle {
+    // the device is moved to a task, it should be connected before
+    pub fn new(device: ModbusDeviceAsync) -> Self {
+        let (queue, rx) = mpsc::unbounded_channel();
+        let state = device.connection_state();
+        tokio::spawn(serve(device, rx));
+        DeviceHandle {
+            queue,
+            state,
+            priority: Priority::default(),
+        }
+    }
+
+    // handle sending its requests with the given priority
+    pub fn with_priority(&self, priority: Priority) -> Self {
+        DeviceHandle {
+            priority,
+            ..self.clone()
+        }
+    }
+
+    pub fn connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.state.clone()
+    }
+
+    // run any operation on the device, ex :
+    // handle.run(|device| Box::pin(async move { device.dump_input_registers().await }))
+    pub async fn run<T, F>(&self, f: F) -> Result<T, ModbusError>
+    where
+        T: Send + 'static,
+        F: for<'a> FnOnce(&'a mut ModbusDeviceAsync) -> BoxFuture<'a, Result<T, ModbusError>>
+            + Send
+            + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job = job(move |device: &mut ModbusDeviceAsync| {
+            Box::pin(async move {
+                // nobody is waiting for the response anymore
+                if tx.is_closed() {
+                    return;
+                }
+                let _ = tx.send(f(device).await);
+            })
+        });
+        self.queue
+            .send((self.priority, job))
+            .map_err(|_| ModbusError::DeviceNotConnectedError)?;
+        rx.await.map_err(|_| ModbusError::DeviceNotConnectedError)?
+    }
+
+    pub async fn read_registers_by_name(
+        &self,
+        names: &[String],
+        source: ModBusRegisters,
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        let names = names.to_vec();
+        self.run(move |device| {
+            Box::pin(async move { device.read_registers_by_name(&names, &source).await })
+        })
+        .await
+    }
+
+    pub async fn read_registers_by_name_detailed(
+        &self,
+        names: &[String],
+        source: ModBusRegisters,
+    ) -> Result<HashMap<String, RegisterResult>, ModbusError> {
+        let names = names.to_vec();
+        self.run(move |device| {
+            Box::pin(async move {
+                device
+                    .read_registers_by_name_detailed(&names, &source)
+                    .await
+            })
+        })
+        .await
+    }
+
+    pub async fn dump_registers(
+        &self,
+        source: ModBusRegisters,
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        self.run(move |device| Box::pin(async move { device.dump_registers(&source).await }))
+            .await
+    }
+
+    pub async fn write_holding_register_by_name(
+        &self,
+        name: &str,
+        val: RegisterValue,
+    ) -> Result<(), ModbusError> {
+        let name = name.to_string();
+        self.run(move |device| {
+            Box::pin(async move { device.write_holding_register_by_name(&name, &val).await })
+        })
+        .await
+    }
+
+    pub async fn mask_write_holding_register_by_name(
+        &self,
+        name: &str,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), ModbusError> {
+        let name = name.to_string();
+        self.run(move |device| {
+            Box::pin(async move {
+                device
+                    .mask_write_holding_register_by_name(&name, and_mask, or_mask)
+                    .await
+            })
+        })
+        .await
+    }
+
+    pub async fn read_write_holding_registers_by_name(
+        &self,
+        write_name: &str,
+        val: RegisterValue,
+        read_names: &[String],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        let write_name = write_name.to_string();
+        let read_names = read_names.to_vec();
+        self.run(move |device| {
+            Box::pin(async move {
+                device
+                    .read_write_holding_registers_by_name(&write_name, &val, &read_names)
+                    .await
+            })
+        })
+        .await
+    }
+
+    pub async fn write_coil_by_name(
+        &self,
+        name: &str,
+        val: RegisterValue,
+    ) -> Result<(), ModbusError> {
+        let name = name.to_string();
+        self.run(move |device| {
+            Box::pin(async move { device.write_coil_by_name(&name, &val).await })
+        })
+        .await
+    }
+
+    pub async fn read_device_identification(
+        &self,
+        code: DeviceIdCode,
+    ) -> Result<DeviceIdentification, ModbusError> {
+        self.run(move |device| {
+            Box::pin(async move { device.read_device_identification(code).await })
+        })
+        .await
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+// objects requested with read device identification (FC43/14), each category includes the previous ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DeviceIdCode {
+    Basic = 0x01,    // vendor name, product code and revision
+    Regular = 0x02,  // vendor url, product name, model name and user application name
+    Extended = 0x03, // private objects (0x80 to 0xFF)
+}
+
+const VENDOR_NAME: u8 = 0x00;
+const PRODUCT_CODE: u8 = 0x01;
+const REVISION: u8 = 0x02;
+const VENDOR_URL: u8 = 0x03;
+const PRODUCT_NAME: u8 = 0x04;
+const MODEL_NAME: u8 = 0x05;
+const USER_APPLICATION_NAME: u8 = 0x06;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceIdentification {
+    pub vendor_name: Option<String>,
+    pub product_code: Option<String>,
+    pub revision: Option<String>,
+    pub vendor_url: Option<String>,
+    pub product_name: Option<String>,
+    pub model_name: Option<String>,
+    pub user_application_name: Option<String>,
+    pub private_objects: BTreeMap<u8, Vec<u8>>, // extended and reserved objects, by id
+    pub conformity_level: u8,
+}
+
+impl DeviceIdentification {
+    // the standard objects are ASCII strings
+    pub fn from_objects(conformity_level: u8, objects: Vec<(u8, Vec<u8>)>) -> Self {
+        let mut identification = DeviceIdentification {
+            conformity_level,
+            ..Default::default()
+        };
+        for (id, value) in objects {
+            let text = Some(String::from_utf8_lossy(&value).into_owned());
+            match id {
+                VENDOR_NAME => identification.vendor_name = text,
+                PRODUCT_CODE => identification.product_code = text,
+                REVISION => identification.revision = text,
+                VENDOR_URL => identification.vendor_url = text,
+                PRODUCT_NAME => identification.product_name = text,
+                MODEL_NAME => identification.model_name = text,
+                USER_APPLICATION_NAME => identification.user_application_name = text,
+                _ => {
+                    identification.private_objects.insert(id, value);
+                }
+            }
+        }
+        identification
+    }
+
+    // objects sent by a device, in order of id
+    pub fn objects(&self) -> BTreeMap<u8, Vec<u8>> {
+        let mut objects = self.private_objects.clone();
+        let standard = [
+            (VENDOR_NAME, &self.vendor_name),
+            (PRODUCT_CODE, &self.product_code),
+            (REVISION, &self.revision),
+            (VENDOR_URL, &self.vendor_url),
+            (PRODUCT_NAME, &self.product_name),
+            (MODEL_NAME, &self.model_name),
+            (USER_APPLICATION_NAME, &self.user_application_name),
+        ];
+        for (id, text) in standard {
+            if let Some(text) = text {
+                objects.insert(id, text.as_bytes().to_vec());
+            }
+        }
+        objects
+    }
+}
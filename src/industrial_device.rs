@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use crate::errors::ModbusError;
 use crate::modbus_connexion_async::ModbusConnexionAsync;
-use crate::register::Register;
+use crate::register::{DataType, Register};
 use crate::types::{ModBusRegisters, RegisterValue};
 use crate::ModbusDeviceAsync;
 
@@ -17,20 +17,119 @@ fn get_register_by_name(
     dev: &mut ModbusDeviceAsync,
     name: &str,
 ) -> Result<(Register, ModBusRegisters), IndustrialDeviceError> {
-    let input_reg = dev.get_input_register_by_name(name);
-    let holding_reg = dev.get_holding_register_by_name(name);
+    let mut found: Vec<(Register, ModBusRegisters)> = [
+        (dev.get_input_register_by_name(name), ModBusRegisters::INPUT),
+        (
+            dev.get_holding_register_by_name(name),
+            ModBusRegisters::HOLDING,
+        ),
+        (
+            dev.get_discrete_input_by_name(name),
+            ModBusRegisters::DISCRETE,
+        ),
+        (dev.get_coil_by_name(name), ModBusRegisters::COIL),
+    ]
+    .into_iter()
+    .filter_map(|(reg, table)| reg.map(|reg| (reg, table)))
+    .collect();
 
-    match (input_reg, holding_reg) {
-        (None, None) => Err(IndustrialDeviceError::RegisterNotFoundError {
+    if found.len() > 1 {
+        warn!(
+            "Found several registers with the same name, returning the {0:?} register ({name})",
+            found[0].1
+        );
+    }
+    if found.is_empty() {
+        return Err(IndustrialDeviceError::RegisterNotFoundError {
             name: name.to_string(),
-        }),
-        (None, Some(val)) => Ok((val, ModBusRegisters::HOLDING)),
-        (Some(val), None) => Ok((val, ModBusRegisters::INPUT)),
-        (Some(val), Some(_)) => {
-            warn!("Found an input register and holding register with the same name, returning the input register ({name})");
-            Ok((val, ModBusRegisters::INPUT))
+        });
+    }
+    Ok(found.swap_remove(0))
+}
+
+// the scaled values are approximations of the raw value, they are rounded to single precision
+fn to_value(val: RegisterValue, reg: Option<&Register>) -> Result<Value, ModbusError> {
+    match val {
+        RegisterValue::Float64(val)
+            if reg.is_some_and(Register::is_scaled) && (val as f32).is_finite() =>
+        {
+            Ok(Value::Float32(val as f32))
+        }
+        val => val.try_into(),
+    }
+}
+
+// the registers whose value can't be represented are reported together
+fn to_values(
+    values: HashMap<String, RegisterValue>,
+    get_register: impl Fn(&str) -> Option<Register>,
+) -> Result<HashMap<String, Value>, ModbusError> {
+    let mut res = HashMap::new();
+    let mut names = Vec::new();
+    for (name, val) in values {
+        match to_value(val, get_register(&name).as_ref()) {
+            Ok(val) => {
+                res.insert(name, val);
+            }
+            Err(_) => names.push(name),
         }
     }
+    if !names.is_empty() {
+        names.sort();
+        return Err(ModbusError::UnrepresentableValueError {
+            names: names.join(", "),
+        });
+    }
+    Ok(res)
+}
+
+// the numeric value in the given type, none if it can't be represented exactly
+fn convert(val: &RegisterValue, data_type: DataType) -> Option<RegisterValue> {
+    let int = match *val {
+        RegisterValue::U16(val) | RegisterValue::Enum16(val) => i128::from(val),
+        RegisterValue::U32(val) => val.into(),
+        RegisterValue::U64(val) => val.into(),
+        RegisterValue::U128(val) => i128::try_from(val).ok()?,
+        RegisterValue::S16(val) => val.into(),
+        RegisterValue::S32(val) => val.into(),
+        RegisterValue::S64(val) => val.into(),
+        RegisterValue::Float32(val) if matches!(data_type, DataType::Float64) => {
+            return Some(RegisterValue::Float64(val.into()))
+        }
+        RegisterValue::Float32(val) if val.fract() == 0.0 => val as i128,
+        _ => return None,
+    };
+    match data_type {
+        DataType::UInt16 => u16::try_from(int).ok().map(RegisterValue::U16),
+        DataType::Enum16 => u16::try_from(int).ok().map(RegisterValue::Enum16),
+        DataType::UInt32 => u32::try_from(int).ok().map(RegisterValue::U32),
+        DataType::UInt64 => u64::try_from(int).ok().map(RegisterValue::U64),
+        DataType::UInt128 => u128::try_from(int).ok().map(RegisterValue::U128),
+        DataType::Int16 => i16::try_from(int).ok().map(RegisterValue::S16),
+        DataType::Int32 => i32::try_from(int).ok().map(RegisterValue::S32),
+        DataType::Int64 => i64::try_from(int).ok().map(RegisterValue::S64),
+        DataType::Float32 => {
+            let float = int as f32;
+            (float as i128 == int).then_some(RegisterValue::Float32(float))
+        }
+        DataType::Float64 => {
+            let float = int as f64;
+            (float as i128 == int).then_some(RegisterValue::Float64(float))
+        }
+        _ => None,
+    }
+}
+
+// values are converted to the type of the register they are written to when it can be done without loss,
+// the others are written as is and rejected if their size doesn't match the register
+fn widen(val: RegisterValue, reg: &Register) -> RegisterValue {
+    match (val, reg.data_type) {
+        (RegisterValue::Sized(bytes), DataType::String(_)) => {
+            let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+            RegisterValue::String(String::from_utf8_lossy(&bytes[..end]).into_owned())
+        }
+        (val, data_type) => convert(&val, data_type).unwrap_or(val),
+    }
 }
 
 #[async_trait]
@@ -43,25 +142,24 @@ impl IndustrialDevice for ModbusDeviceAsync {
     async fn dump_registers(&mut self) -> Result<HashMap<String, Value>, IndustrialDeviceError> {
         let input: HashMap<String, RegisterValue> = self.dump_input_registers().await?;
         let holding: HashMap<String, RegisterValue> = self.dump_holding_registers().await?;
+        let discrete: HashMap<String, RegisterValue> = self.dump_discrete_inputs().await?;
+        let coils: HashMap<String, RegisterValue> = self.dump_coils().await?;
 
-        let input_conv: HashMap<String, Value> = input
-            .iter()
-            .map(|(name, val)| (name.clone(), Into::<Value>::into(*val)))
-            .collect();
-        let holding_conv: HashMap<String, Value> = holding
-            .iter()
-            .map(|(name, val)| (name.clone(), Into::<Value>::into(*val)))
-            .collect();
-
-        let mut res = input_conv;
-        res.extend(holding_conv);
+        let mut res = to_values(input, |name| self.get_input_register_by_name(name))?;
+        res.extend(to_values(holding, |name| {
+            self.get_holding_register_by_name(name)
+        })?);
+        res.extend(to_values(discrete, |name| {
+            self.get_discrete_input_by_name(name)
+        })?);
+        res.extend(to_values(coils, |name| self.get_coil_by_name(name))?);
         Ok(res)
     }
 
     async fn read_register_by_name(&mut self, name: &str) -> Result<Value, IndustrialDeviceError> {
         let (reg, table) = get_register_by_name(self, name)?;
         let val = self.read_register(&reg, &table).await?;
-        Ok(val.into())
+        Ok(to_value(val, Some(&reg))?)
     }
 
     async fn write_register_by_name(
@@ -70,7 +168,19 @@ impl IndustrialDevice for ModbusDeviceAsync {
         value: &Value,
     ) -> Result<(), IndustrialDeviceError> {
         let val: RegisterValue = value.clone().into();
-        self.write_holding_register_by_name(name, &val).await?;
+        match self.get_coil_by_name(name) {
+            Some(reg) if self.get_holding_register_by_name(name).is_none() => {
+                self.write_coil(&reg, &val).await?
+            }
+            _ => {
+                let reg = self.get_holding_register_by_name(name).ok_or(
+                    IndustrialDeviceError::RegisterNotFoundError {
+                        name: name.to_string(),
+                    },
+                )?;
+                self.write_holding_register(&reg, &widen(val, &reg)).await?
+            }
+        }
         Ok(())
     }
 }
@@ -107,23 +217,68 @@ impl From<ModbusError> for IndustrialDeviceError {
             ModbusError::RegisterDoesNotExistError { name } => {
                 IndustrialDeviceError::RegisterNotFoundError { name }
             }
+            ModbusError::UnsupportedTableError => IndustrialDeviceError::RequestError {
+                err: Box::new(value),
+            },
+            ModbusError::Timeout => IndustrialDeviceError::DeviceNotAccessibleError {
+                err: Box::new(value),
+            },
+            ModbusError::SerialError { err } => {
+                IndustrialDeviceError::DeviceNotAccessibleError { err: Box::new(err) }
+            }
+            ModbusError::UnexpectedResponseError => IndustrialDeviceError::RequestError {
+                err: Box::new(value),
+            },
+            ModbusError::UnknownLabelError { .. } => IndustrialDeviceError::ConversionError {
+                err: Box::new(value),
+            },
+            ModbusError::UnrepresentableValueError { .. } => {
+                IndustrialDeviceError::ConversionError {
+                    err: Box::new(value),
+                }
+            }
+            ModbusError::QuantityError => IndustrialDeviceError::RequestError {
+                err: Box::new(value),
+            },
         }
     }
 }
 
-impl From<RegisterValue> for Value {
-    fn from(value: RegisterValue) -> Self {
-        match value {
+// values have no 64 bits signed, floating point or string type, they are narrowed when possible
+impl TryFrom<RegisterValue> for Value {
+    type Error = ModbusError;
+
+    fn try_from(value: RegisterValue) -> Result<Self, Self::Error> {
+        Ok(match value {
             RegisterValue::U16(val) => Value::U16(val),
             RegisterValue::U32(val) => Value::U32(val),
             RegisterValue::U64(val) => Value::U64(val),
             RegisterValue::U128(val) => Value::U128(val),
+            RegisterValue::S16(val) => Value::S16(val),
             RegisterValue::S32(val) => Value::S32(val),
+            RegisterValue::S64(val) => {
+                Value::S32(i32::try_from(val).map_err(|_| ModbusError::ConversionError)?)
+            }
             RegisterValue::Enum16(val) => Value::Enum16(val),
+            RegisterValue::Label(val, _) => Value::Enum16(val),
             RegisterValue::Sized(val) => Value::Sized(val),
             RegisterValue::Float32(val) => Value::Float32(val),
+            // only the values that are exact in single precision
+            RegisterValue::Float64(val) => match val as f32 {
+                float if float as f64 == val || val.is_nan() => Value::Float32(float),
+                _ => return Err(ModbusError::ConversionError),
+            },
             RegisterValue::Boolean(val) => Value::Boolean(val),
-        }
+            // strings are NUL padded
+            RegisterValue::String(val) => {
+                let mut bytes = val.into_bytes();
+                if bytes.len() > 66 {
+                    return Err(ModbusError::ConversionError);
+                }
+                bytes.resize(66, 0);
+                Value::Sized(bytes.try_into().map_err(|_| ModbusError::ConversionError)?)
+            }
+        })
     }
 }
 
@@ -134,7 +289,7 @@ impl From<Value> for RegisterValue {
             Value::U32(val) => RegisterValue::U32(val),
             Value::U64(val) => RegisterValue::U64(val),
             Value::U128(val) => RegisterValue::U128(val),
-            Value::S16(val) => RegisterValue::S32(val as i32),
+            Value::S16(val) => RegisterValue::S16(val),
             Value::S32(val) => RegisterValue::S32(val),
             Value::Enum16(val) => RegisterValue::Enum16(val),
             Value::Sized(val) => RegisterValue::Sized(val),
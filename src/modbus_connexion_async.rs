@@ -1,16 +1,18 @@
 use std::collections::HashMap;
 
-use tokio_modbus::{Address, Quantity};
+use tokio_modbus::{Address, Quantity, Slave};
 
 use crate::{
     errors::ModbusError,
+    identification::{DeviceIdCode, DeviceIdentification},
     register::Register,
-    types::{ModBusRegisters, RegisterValue},
+    types::{ModBusRegisters, RegisterResult, RegisterValue},
 };
 
 #[trait_variant::make(ModbusConnexionAsync: Send)]
 pub trait LocalModbusConnexionAsync {
     async fn connect(&mut self) -> Result<(), ModbusError>;
+    fn set_slave(&mut self, slave: Slave);
 
     // Lower level utils
     async fn read_raw_registers(
@@ -19,16 +21,61 @@ pub trait LocalModbusConnexionAsync {
         nb: &Quantity,
         source: &ModBusRegisters,
     ) -> Result<Vec<u16>, ModbusError>;
+    async fn read_raw_bits(
+        &mut self,
+        addr: &Address,
+        nb: &Quantity,
+        source: &ModBusRegisters,
+    ) -> Result<Vec<bool>, ModbusError>;
+    async fn write_raw_holding_register(
+        &mut self,
+        addr: &Address,
+        value: u16,
+    ) -> Result<(), ModbusError>;
     async fn write_raw_holding_registers(
         &mut self,
         addr: &Address,
         data: &[u16],
     ) -> Result<(), ModbusError>;
+    async fn write_raw_coils(&mut self, addr: &Address, data: &[bool]) -> Result<(), ModbusError>;
+    async fn mask_write_raw_register(
+        &mut self,
+        addr: &Address,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), ModbusError>;
+    async fn read_write_raw_registers(
+        &mut self,
+        read_addr: &Address,
+        read_nb: &Quantity,
+        write_addr: &Address,
+        data: &[u16],
+    ) -> Result<Vec<u16>, ModbusError>;
+    async fn read_device_identification(
+        &mut self,
+        code: DeviceIdCode,
+    ) -> Result<DeviceIdentification, ModbusError>;
     async fn read_range(
         &mut self,
         regs: &[Register],
         source: &ModBusRegisters,
-    ) -> Result<HashMap<String, RegisterValue>, ModbusError>;
+    ) -> Result<HashMap<String, RegisterResult>, ModbusError>;
+
+    // Global access reporting the outcome of each register
+    async fn read_registers_detailed(
+        &mut self,
+        regs: &[Register],
+        source: &ModBusRegisters,
+    ) -> Result<HashMap<String, RegisterResult>, ModbusError>;
+    async fn read_registers_by_name_detailed(
+        &mut self,
+        names: &[String],
+        source: &ModBusRegisters,
+    ) -> Result<HashMap<String, RegisterResult>, ModbusError>;
+    async fn dump_registers_detailed(
+        &mut self,
+        source: &ModBusRegisters,
+    ) -> Result<HashMap<String, RegisterResult>, ModbusError>;
 
     // Global access
     async fn read_registers(
@@ -87,8 +134,63 @@ pub trait LocalModbusConnexionAsync {
         name: &str,
         val: &RegisterValue,
     ) -> Result<(), ModbusError>;
+    async fn mask_write_holding_register(
+        &mut self,
+        reg: &Register,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), ModbusError>;
+    async fn mask_write_holding_register_by_name(
+        &mut self,
+        name: &str,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), ModbusError>;
+    async fn read_write_holding_registers(
+        &mut self,
+        write_reg: &Register,
+        val: &RegisterValue,
+        read_regs: &[Register],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError>;
+    async fn read_write_holding_registers_by_name(
+        &mut self,
+        write_name: &str,
+        val: &RegisterValue,
+        read_names: &[String],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError>;
+
+    // Coil specific wrappers
+    async fn read_coils_by_name(
+        &mut self,
+        names: &[String],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError>;
+    async fn read_coils(
+        &mut self,
+        regs: &[Register],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError>;
+    async fn dump_coils(&mut self) -> Result<HashMap<String, RegisterValue>, ModbusError>;
+    async fn write_coil(&mut self, reg: &Register, val: &RegisterValue) -> Result<(), ModbusError>;
+    async fn write_coil_by_name(
+        &mut self,
+        name: &str,
+        val: &RegisterValue,
+    ) -> Result<(), ModbusError>;
+
+    // Discrete input specific wrappers
+    async fn read_discrete_inputs_by_name(
+        &mut self,
+        names: &[String],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError>;
+    async fn read_discrete_inputs(
+        &mut self,
+        regs: &[Register],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError>;
+    async fn dump_discrete_inputs(&mut self)
+        -> Result<HashMap<String, RegisterValue>, ModbusError>;
 
     // Registers access utils
     fn get_holding_register_by_name(&mut self, name: &str) -> Option<Register>;
     fn get_input_register_by_name(&mut self, name: &str) -> Option<Register>;
+    fn get_coil_by_name(&mut self, name: &str) -> Option<Register>;
+    fn get_discrete_input_by_name(&mut self, name: &str) -> Option<Register>;
 }
@@ -1,39 +1,215 @@
-use log::{debug, warn};
-use std::collections::HashMap;
-use tokio_modbus::{
-    client::{rtu, tcp, Context},
-    prelude::{Reader, Writer},
-    Address, Quantity,
-};
+use log::{debug, info, warn};
+use std::{collections::HashMap, future::Future, time::Duration};
+use tokio_modbus::{client::tcp, Address, Exception, Quantity, Slave};
 
-use tokio_serial::SerialStream;
-use tokio_serial::{self, StopBits};
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
 
+pub mod ascii;
+pub mod deadband;
 pub mod errors;
+pub mod handle;
+pub mod identification;
 pub mod industrial_device;
 pub mod modbus_connexion_async;
+pub mod pdu;
+pub mod pipeline;
+pub mod planner;
+pub mod poller;
+pub mod reconnect;
 pub mod register;
+pub mod rtu;
+pub mod rtu_bus;
+pub mod server;
+pub mod transport;
 pub mod types;
+pub mod udp;
 pub mod utils;
 
-use crate::register::Register;
-use crate::types::RegisterValue;
+use crate::identification::{DeviceIdCode, DeviceIdentification};
+use crate::pdu::{Request, Response};
+use crate::pipeline::PipelinedTcp;
+use crate::planner::ReadPlanner;
+use crate::reconnect::{ConnectionState, ReconnectPolicy};
+use crate::register::{Endianness, Register, WriteStrategy};
+use crate::types::{RegisterResult, RegisterValue};
 use crate::{
-    errors::ModbusError,
-    types::{ModBusContext, ModBusRegisters},
+    errors::{ModbusError, RegisterError},
+    types::{BitWriteMode, ModBusContext, ModBusRegisters},
 };
 
 use crate::modbus_connexion_async::ModbusConnexionAsync;
+use crate::rtu::{RtuTransport, MIN_FRAME_GAP};
+use crate::transport::Transport;
+use crate::udp::UdpTransport;
 
 // maximum number of register that can be read at once (limited by the protocol)
 const MODBUS_MAX_READ_LEN: u16 = 125;
+// maximum number of coils or discrete inputs that can be read at once (limited by the protocol)
+const MODBUS_MAX_READ_BITS: u16 = 2000;
+
+// wait for the response of a request, failing if it does not come in time
+async fn transaction<T>(
+    timeout: Option<Duration>,
+    request: impl Future<Output = Result<T, ModbusError>>,
+) -> Result<T, ModbusError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, request)
+            .await
+            .map_err(|_| ModbusError::Timeout)?,
+        None => request.await,
+    }
+}
+
+// the exception returned for a range concerns all of its registers
+fn range_exception(regs: &[Register], err: Exception) -> HashMap<String, RegisterResult> {
+    regs.iter()
+        .map(|v| (v.name.to_owned(), Err(RegisterError::Exception { err })))
+        .collect()
+}
+
+// first address and length of a range, the registers can overlap but not go past the last address
+fn range_bounds(regs: &[Register]) -> Result<(Address, Quantity), ModbusError> {
+    let start = regs.first().unwrap().addr;
+    let end = regs
+        .iter()
+        .map(|r| u32::from(r.addr) + u32::from(r.len))
+        .max()
+        .unwrap();
+    let nb = Quantity::try_from(end - u32::from(start)).map_err(|_| ModbusError::QuantityError)?;
+    if end > 0x10000 {
+        return Err(ModbusError::QuantityError);
+    }
+    Ok((start, nb))
+}
+
+fn range_request(regs: &[Register], source: &ModBusRegisters) -> Result<Request, ModbusError> {
+    let (addr, nb) = range_bounds(regs)?;
+    Ok(match source {
+        ModBusRegisters::INPUT => Request::ReadInputRegisters(addr, nb),
+        ModBusRegisters::HOLDING => Request::ReadHoldingRegisters(addr, nb),
+        ModBusRegisters::COIL => Request::ReadCoils(addr, nb),
+        ModBusRegisters::DISCRETE => Request::ReadDiscreteInputs(addr, nb),
+    })
+}
+
+// associate the values read for a range with its registers
+fn range_values(
+    regs: &[Register],
+    response: Result<Response, ModbusError>,
+    default_endianness: Endianness,
+) -> Result<HashMap<String, RegisterResult>, ModbusError> {
+    let (start, nb) = range_bounds(regs)?;
+    match response {
+        Ok(Response::ReadCoils(bits) | Response::ReadDiscreteInputs(bits))
+            if bits.len() >= usize::from(nb) =>
+        {
+            Ok(regs
+                .iter()
+                .map(|v| {
+                    let off: usize = (v.addr - start).into();
+                    (v.name.to_owned(), Ok(RegisterValue::Boolean(bits[off])))
+                })
+                .collect())
+        }
+        Ok(Response::ReadInputRegisters(words) | Response::ReadHoldingRegisters(words))
+            if words.len() >= usize::from(nb) =>
+        {
+            // convert them to the types and make the association with the registers
+            Ok(regs
+                .iter()
+                .map(|v| {
+                    let start_off = v.addr - start;
+                    let value: Vec<u16> =
+                        words[start_off.into()..(start_off + v.len).into()].to_vec();
+                    (v.name.to_owned(), v.decode(value, default_endianness))
+                })
+                .collect())
+        }
+        Ok(_) => Err(ModbusError::UnexpectedResponseError),
+        Err(ModbusError::Exception { err }) => Ok(range_exception(regs, err)),
+        Err(err) => Err(err),
+    }
+}
+
+// send the reads of all the ranges without waiting for the previous responses
+async fn read_ranges_pipelined(
+    pipeline: &PipelinedTcp,
+    ranges: &[Vec<Register>],
+    source: &ModBusRegisters,
+    default_endianness: Endianness,
+    timeout: Option<Duration>,
+) -> Result<HashMap<String, RegisterResult>, ModbusError> {
+    let requests = ranges
+        .iter()
+        .map(|range| range_request(range, source))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut reads = JoinSet::new();
+    for (i, request) in requests.into_iter().enumerate() {
+        let pipeline = pipeline.clone();
+        debug!("reading range {request:?} (pipelined)");
+        reads.spawn(async move { (i, transaction(timeout, pipeline.send(&request)).await) });
+    }
+
+    // all the reads are waited for, a request dropped while being sent would leave a partial frame
+    let mut result = HashMap::new();
+    let mut error = None;
+    while let Some(res) = reads.join_next().await {
+        let values = res
+            .map_err(|err| ModbusError::IOerror { err: err.into() })
+            .and_then(|(i, response)| range_values(&ranges[i], response, default_endianness));
+        match values {
+            Ok(values) => result.extend(values),
+            Err(err) => {
+                error.get_or_insert(err);
+            }
+        }
+    }
+    match error {
+        Some(err) => Err(err),
+        None => Ok(result),
+    }
+}
+
+// keep the registers that could be read, the device exceptions fail the whole read
+fn read_values(
+    results: HashMap<String, RegisterResult>,
+) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+    let mut values = HashMap::new();
+    for (name, res) in results {
+        match res {
+            Ok(val) => {
+                values.insert(name, val);
+            }
+            Err(RegisterError::Exception { err }) => return Err(err.into()),
+            Err(err) => warn!("Could not read register {name}, dropping it ({err})"),
+        }
+    }
+    Ok(values)
+}
 
 #[derive(Debug)]
 pub struct ModbusDeviceAsync {
-    ctx: Option<Context>,
+    ctx: Option<Box<dyn Transport>>,
     input_registers: HashMap<String, Register>,
     holding_registers: HashMap<String, Register>,
+    coils: HashMap<String, Register>,
+    discrete_inputs: HashMap<String, Register>,
     device: ModBusContext,
+    // byte ordering of the registers that don't specify one
+    endianness: Endianness,
+    reconnect_policy: Option<ReconnectPolicy>,
+    planner: ReadPlanner,
+    // maximum number of requests in flight, TCP only
+    pipeline_window: Option<usize>,
+    pipeline: Option<PipelinedTcp>,
+    bit_write_mode: BitWriteMode,
+    // function used for the registers that don't specify one
+    write_strategy: WriteStrategy,
+    state: watch::Sender<ConnectionState>,
+    last_transaction: Option<Instant>,
 }
 
 impl ModbusDeviceAsync {
@@ -46,31 +222,294 @@ impl ModbusDeviceAsync {
             ctx: None,
             input_registers,
             holding_registers,
+            coils: HashMap::new(),
+            discrete_inputs: HashMap::new(),
             device: context,
+            endianness: Endianness::default(),
+            reconnect_policy: None,
+            planner: ReadPlanner::default(),
+            pipeline_window: None,
+            pipeline: None,
+            bit_write_mode: BitWriteMode::default(),
+            write_strategy: WriteStrategy::default(),
+            state: watch::channel(ConnectionState::Disconnected).0,
+            last_transaction: None,
         }
     }
-}
 
-impl ModbusConnexionAsync for ModbusDeviceAsync {
-    async fn connect(&mut self) -> Result<(), ModbusError> {
+    // byte ordering used for the registers that don't define their own
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    // coils definitions, each register is a single bit (len = 1)
+    pub fn with_coils(mut self, coils: HashMap<String, Register>) -> Self {
+        self.coils = coils;
+        self
+    }
+
+    // discrete inputs definitions, each register is a single bit (len = 1)
+    pub fn with_discrete_inputs(mut self, discrete_inputs: HashMap<String, Register>) -> Self {
+        self.discrete_inputs = discrete_inputs;
+        self
+    }
+
+    // how the registers are grouped into requests
+    pub fn with_read_planner(mut self, planner: ReadPlanner) -> Self {
+        self.planner = planner;
+        self
+    }
+
+    // send the reads of the ranges planned without waiting for the previous responses,
+    // for the TCP devices that accept several requests in flight
+    pub fn with_pipelining(mut self, window: usize) -> Self {
+        self.pipeline_window = Some(window);
+        self
+    }
+
+    // how the holding registers with a mask are written
+    pub fn with_bit_write_mode(mut self, mode: BitWriteMode) -> Self {
+        self.bit_write_mode = mode;
+        self
+    }
+
+    // function used to write the holding registers that don't define their own
+    pub fn with_write_strategy(mut self, strategy: WriteStrategy) -> Self {
+        self.write_strategy = strategy;
+        self
+    }
+
+    fn get_table(&self, source: &ModBusRegisters) -> &HashMap<String, Register> {
+        match source {
+            ModBusRegisters::INPUT => &self.input_registers,
+            ModBusRegisters::HOLDING => &self.holding_registers,
+            ModBusRegisters::COIL => &self.coils,
+            ModBusRegisters::DISCRETE => &self.discrete_inputs,
+        }
+    }
+
+    // get notified of the connection state changes
+    pub fn connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.state.subscribe()
+    }
+
+    // re-establish the connection automatically when it is lost
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = Some(policy);
+        self
+    }
+
+    fn should_reconnect(&self, err: &ModbusError) -> bool {
+        if self.reconnect_policy.is_none() {
+            return false;
+        }
+        match err {
+            // the connection was lost and could not be re-established yet
+            ModbusError::DeviceNotConnectedError => {
+                *self.state.borrow() != ConnectionState::Disconnected
+            }
+            err => err.is_connection_error(),
+        }
+    }
+
+    // the connection is in an unknown state, it is closed until the device is connected again
+    fn drop_connection(&mut self) {
+        self.ctx = None;
+        self.pipeline = None;
+        self.state.send_replace(ConnectionState::Disconnected);
+    }
+
+    async fn reconnect(&mut self) -> Result<(), ModbusError> {
+        let policy = self
+            .reconnect_policy
+            .clone()
+            .ok_or(ModbusError::DeviceNotConnectedError)?;
+        self.ctx = None;
+        self.pipeline = None;
+
+        let mut attempt = 1;
+        loop {
+            self.state
+                .send_replace(ConnectionState::Reconnecting { attempt });
+            tokio::time::sleep(policy.delay(attempt)).await;
+
+            match self.open().await {
+                Ok(()) => {
+                    info!("Reconnected to device {0:?}", self.device);
+                    self.state.send_replace(ConnectionState::Connected);
+                    return Ok(());
+                }
+                Err(err) => {
+                    warn!("Reconnection attempt {attempt} failed ({err})");
+                    if policy.max_attempts.is_some_and(|max| attempt >= max) {
+                        self.state.send_replace(ConnectionState::Failed);
+                        return Err(err);
+                    }
+                }
+            }
+            attempt += 1;
+        }
+    }
+
+    // wait for the inter-request delay and get the response timeout
+    async fn begin_transaction(&mut self) -> Option<Duration> {
+        let timeouts = self.device.timeouts();
+        if let (Some(delay), Some(last)) = (timeouts.inter_request, self.last_transaction) {
+            tokio::time::sleep_until(last + delay).await;
+        }
+        match self.device {
+            // applied by the bus once the line is acquired
+            ModBusContext::RTUBus(_) => None,
+            _ => timeouts.response,
+        }
+    }
+
+    async fn open(&mut self) -> Result<(), ModbusError> {
         match &self.device {
+            ModBusContext::TCP(ctx) if self.pipeline_window.is_some() => {
+                let window = self.pipeline_window.unwrap_or_default();
+                let connection = PipelinedTcp::connect(ctx.addr, ctx.slave, window);
+                let connection = match ctx.timeouts.connect {
+                    Some(timeout) => tokio::time::timeout(timeout, connection)
+                        .await
+                        .map_err(|_| ModbusError::Timeout)?,
+                    None => connection.await,
+                }?;
+                self.pipeline = Some(connection.clone());
+                self.ctx = Some(Box::new(connection));
+            }
             ModBusContext::TCP(ctx) => {
-                self.ctx = Some(tcp::connect(ctx.addr).await?);
+                let connection = tcp::connect_slave(ctx.addr, ctx.slave);
+                let connection = match ctx.timeouts.connect {
+                    Some(timeout) => tokio::time::timeout(timeout, connection)
+                        .await
+                        .map_err(|_| ModbusError::Timeout)?,
+                    None => connection.await,
+                };
+                self.ctx = Some(Box::new(connection?));
             }
             ModBusContext::RTU(ctx) => {
-                let builder = tokio_serial::new(ctx.port.clone(), ctx.speed)
-                    .stop_bits(StopBits::Two)
-                    .parity(tokio_serial::Parity::None)
-                    .data_bits(tokio_serial::DataBits::Eight);
-                let port = SerialStream::open(&builder).unwrap();
-
-                self.ctx = Some(rtu::attach_slave(port, ctx.slave));
+                self.ctx = Some(ctx.open_transport(ctx.slave)?);
                 debug!("Connected to devices {0:?}", self.ctx);
             }
+            ModBusContext::RTUBus(ctx) => {
+                self.ctx = Some(Box::new(
+                    ctx.bus.transport(ctx.slave, ctx.timeouts.response).await?,
+                ));
+            }
+            ModBusContext::RTUOverTCP(ctx) => {
+                let stream = TcpStream::connect(ctx.addr);
+                let stream = match ctx.timeouts.connect {
+                    Some(timeout) => tokio::time::timeout(timeout, stream)
+                        .await
+                        .map_err(|_| ModbusError::Timeout)?,
+                    None => stream.await,
+                }?;
+                // the speed of the line behind the converter is unknown
+                self.ctx = Some(Box::new(RtuTransport::new(
+                    stream,
+                    ctx.slave,
+                    MIN_FRAME_GAP,
+                )));
+            }
+            ModBusContext::UDP(ctx) => {
+                let transport =
+                    UdpTransport::connect(ctx.addr, ctx.slave, ctx.retry_timeout, ctx.retries);
+                self.ctx = Some(Box::new(transport.await?));
+            }
         }
         Ok(())
     }
 
+    async fn write_words(
+        &mut self,
+        reg: &Register,
+        addr: Address,
+        data: &[u16],
+    ) -> Result<(), ModbusError> {
+        match (reg.write_strategy.unwrap_or(self.write_strategy), data) {
+            (WriteStrategy::Auto, [value]) => self.write_raw_holding_register(&addr, *value).await,
+            (WriteStrategy::Single, data) => {
+                // nothing is written if the last words would go past the last address
+                let addresses = (0..data.len())
+                    .map(|i| {
+                        Address::try_from(i)
+                            .ok()
+                            .and_then(|i| addr.checked_add(i))
+                            .ok_or(ModbusError::QuantityError)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                for (addr, value) in addresses.into_iter().zip(data) {
+                    self.write_raw_holding_register(&addr, *value).await?;
+                }
+                Ok(())
+            }
+            (WriteStrategy::Auto | WriteStrategy::Multiple, data) => {
+                self.write_raw_holding_registers(&addr, data).await
+            }
+        }
+    }
+
+    // send a request to the device, applying the timeouts
+    async fn try_call(&mut self, request: Request) -> Result<Response, ModbusError> {
+        request
+            .check_quantity()
+            .map_err(|_| ModbusError::QuantityError)?;
+        let timeout = self.begin_transaction().await;
+        let ctx = self
+            .ctx
+            .as_mut()
+            .ok_or(ModbusError::DeviceNotConnectedError)?;
+        let res = transaction(timeout, ctx.call(request)).await;
+        self.last_transaction = Some(Instant::now());
+        res
+    }
+
+    // reads are sent again once reconnected, writes only if nothing was sent to the device
+    async fn call(&mut self, request: Request) -> Result<Response, ModbusError> {
+        let res = match self.try_call(request.clone()).await {
+            Err(err) if self.should_reconnect(&err) => {
+                warn!("Connection to the device lost ({err}), reconnecting");
+                self.reconnect().await?;
+                match (request.is_read(), err) {
+                    (true, _) | (false, ModbusError::DeviceNotConnectedError) => {
+                        self.try_call(request).await
+                    }
+                    (false, err) => Err(err),
+                }
+            }
+            res => res,
+        };
+        // a late response would be taken for the response of the next request, the UDP responses
+        // are matched to their request and the bus drops the late responses itself
+        if matches!(res, Err(ModbusError::Timeout))
+            && !matches!(
+                self.device,
+                ModBusContext::UDP(_) | ModBusContext::RTUBus(_)
+            )
+        {
+            warn!("No response from the device in time, closing the connection");
+            self.drop_connection();
+        }
+        res
+    }
+}
+
+impl ModbusConnexionAsync for ModbusDeviceAsync {
+    async fn connect(&mut self) -> Result<(), ModbusError> {
+        match self.open().await {
+            Ok(()) => {
+                self.state.send_replace(ConnectionState::Connected);
+                Ok(())
+            }
+            Err(err) => {
+                self.state.send_replace(ConnectionState::Failed);
+                Err(err)
+            }
+        }
+    }
+
     // read input registers by address
     async fn read_raw_registers(
         &mut self,
@@ -79,21 +518,49 @@ impl ModbusConnexionAsync for ModbusDeviceAsync {
         source: &ModBusRegisters,
     ) -> Result<Vec<u16>, ModbusError> {
         debug!("read register {addr} x{nb}");
-        let ctx = self
-            .ctx
-            .as_mut()
-            .ok_or(ModbusError::DeviceNotConnectedError)?;
-        let res = match source {
-            ModBusRegisters::INPUT => ctx.read_input_registers(*addr, *nb),
-            ModBusRegisters::HOLDING => ctx.read_holding_registers(*addr, *nb),
+        let request = match source {
+            ModBusRegisters::INPUT => Request::ReadInputRegisters(*addr, *nb),
+            ModBusRegisters::HOLDING => Request::ReadHoldingRegisters(*addr, *nb),
+            ModBusRegisters::COIL | ModBusRegisters::DISCRETE => {
+                return Err(ModbusError::UnsupportedTableError)
+            }
+        };
+        match self.call(request).await? {
+            Response::ReadInputRegisters(res) | Response::ReadHoldingRegisters(res) => Ok(res),
+            _ => Err(ModbusError::UnexpectedResponseError),
         }
-        .await;
-        match res {
-            Ok(res) => match res {
-                Ok(res) => return Ok(res),
-                Err(err) => Err(err.into()),
-            },
-            Err(err) => return Err(err.into()),
+    }
+    // read coils or discrete inputs by address
+    async fn read_raw_bits(
+        &mut self,
+        addr: &Address,
+        nb: &Quantity,
+        source: &ModBusRegisters,
+    ) -> Result<Vec<bool>, ModbusError> {
+        debug!("read bits {addr} x{nb}");
+        let request = match source {
+            ModBusRegisters::COIL => Request::ReadCoils(*addr, *nb),
+            ModBusRegisters::DISCRETE => Request::ReadDiscreteInputs(*addr, *nb),
+            ModBusRegisters::INPUT | ModBusRegisters::HOLDING => {
+                return Err(ModbusError::UnsupportedTableError)
+            }
+        };
+        match self.call(request).await? {
+            Response::ReadCoils(res) | Response::ReadDiscreteInputs(res) => Ok(res),
+            _ => Err(ModbusError::UnexpectedResponseError),
+        }
+    }
+    async fn write_raw_holding_register(
+        &mut self,
+        addr: &Address,
+        value: u16,
+    ) -> Result<(), ModbusError> {
+        match self
+            .call(Request::WriteSingleRegister(*addr, value))
+            .await?
+        {
+            Response::WriteSingleRegister(..) => Ok(()),
+            _ => Err(ModbusError::UnexpectedResponseError),
         }
     }
     async fn write_raw_holding_registers(
@@ -101,149 +568,166 @@ impl ModbusConnexionAsync for ModbusDeviceAsync {
         addr: &Address,
         data: &[u16],
     ) -> Result<(), ModbusError> {
-        let ctx = self
-            .ctx
-            .as_mut()
-            .ok_or(ModbusError::DeviceNotConnectedError)?;
-        let res = ctx.write_multiple_registers(*addr, data).await;
-        match res {
-            Ok(res) => match res {
-                Ok(res) => return Ok(res),
-                Err(err) => Err(err.into()),
-            },
-            Err(err) => return Err(err.into()),
+        match self
+            .call(Request::WriteMultipleRegisters(*addr, data.to_vec()))
+            .await?
+        {
+            Response::WriteMultipleRegisters(..) => Ok(()),
+            _ => Err(ModbusError::UnexpectedResponseError),
+        }
+    }
+    async fn write_raw_coils(&mut self, addr: &Address, data: &[bool]) -> Result<(), ModbusError> {
+        let request = match data {
+            [coil] => Request::WriteSingleCoil(*addr, *coil),
+            _ => Request::WriteMultipleCoils(*addr, data.to_vec()),
+        };
+        match self.call(request).await? {
+            Response::WriteSingleCoil(..) | Response::WriteMultipleCoils(..) => Ok(()),
+            _ => Err(ModbusError::UnexpectedResponseError),
+        }
+    }
+    // the register becomes (current & and_mask) | (or_mask & !and_mask)
+    async fn mask_write_raw_register(
+        &mut self,
+        addr: &Address,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), ModbusError> {
+        match self
+            .call(Request::MaskWriteRegister(*addr, and_mask, or_mask))
+            .await?
+        {
+            Response::MaskWriteRegister(..) => Ok(()),
+            _ => Err(ModbusError::UnexpectedResponseError),
+        }
+    }
+    // write holding registers then read holding registers in the same transaction
+    async fn read_write_raw_registers(
+        &mut self,
+        read_addr: &Address,
+        read_nb: &Quantity,
+        write_addr: &Address,
+        data: &[u16],
+    ) -> Result<Vec<u16>, ModbusError> {
+        debug!(
+            "write register {write_addr} x{0} then read {read_addr} x{read_nb}",
+            data.len()
+        );
+        let request =
+            Request::ReadWriteMultipleRegisters(*read_addr, *read_nb, *write_addr, data.to_vec());
+        match self.call(request).await? {
+            Response::ReadWriteMultipleRegisters(res) => Ok(res),
+            _ => Err(ModbusError::UnexpectedResponseError),
+        }
+    }
+    // the objects may be sent in several parts, each request starts where the previous one stopped
+    async fn read_device_identification(
+        &mut self,
+        code: DeviceIdCode,
+    ) -> Result<DeviceIdentification, ModbusError> {
+        let mut objects = Vec::new();
+        let mut object_id = 0x00;
+        // there are at most 256 objects, a device repeating itself must not loop forever
+        for _ in 0..=u8::MAX {
+            let request = Request::ReadDeviceIdentification(code as u8, object_id);
+            let Response::ReadDeviceIdentification(part) = self.call(request).await? else {
+                return Err(ModbusError::UnexpectedResponseError);
+            };
+            debug!(
+                "received {0} identification objects from {object_id}",
+                part.objects.len()
+            );
+            objects.extend(part.objects);
+            if !part.more_follows {
+                return Ok(DeviceIdentification::from_objects(
+                    part.conformity_level,
+                    objects,
+                ));
+            }
+            object_id = part.next_object_id;
         }
+        Err(ModbusError::UnexpectedResponseError)
     }
     async fn read_range(
         &mut self,
         regs: &[Register],
         source: &ModBusRegisters,
-    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
-        let s_reg = regs.first().unwrap();
-        let e_reg = regs.last().unwrap();
-        // Read the values
-
-        let start_address = s_reg.addr;
-        let read_len = e_reg.addr + e_reg.len - s_reg.addr;
-
-        debug!("reading range {0}:{1}", start_address, read_len);
-
-        let read_regs: Vec<u16> = self
-            .read_raw_registers(&start_address, &read_len, source)
-            .await?;
-
-        // convert them to the types and make the association with the registers
-        Ok(regs
-            .iter()
-            .filter_map(|v| {
-                let start_off = v.addr - start_address;
-                let value: Vec<u16> =
-                    read_regs[start_off.into()..(start_off + v.len).into()].to_vec();
-                match (value, v.data_type).try_into() {
-                    Ok(res) => Some((v.name.to_owned(), res)),
-                    Err(err) => {
-                        warn!(
-                            "There was an error converting field {0} dropping it ({err:?})",
-                            v.name
-                        );
-                        None
-                    }
-                }
-            })
-            .collect())
+    ) -> Result<HashMap<String, RegisterResult>, ModbusError> {
+        let request = range_request(regs, source)?;
+        debug!("reading range {request:?}");
+        let response = self.call(request).await;
+        range_values(regs, response, self.endianness)
     }
 
-    async fn read_registers(
+    async fn read_registers_detailed(
         &mut self,
         regs: &[Register],
         source: &ModBusRegisters,
-    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
-        // read registers in order of address
-        let mut sorted_regs = regs.to_vec();
-        sorted_regs.sort_by_key(|s| s.addr);
-
-        // index of the start and end register for the current range
-        let mut reg_range_start = 0;
-        let mut reg_range_end = 0;
-
-        let mut result: HashMap<String, RegisterValue> = HashMap::new();
-
-        if sorted_regs.len() == 0 {
+    ) -> Result<HashMap<String, RegisterResult>, ModbusError> {
+        let ranges = self.planner.plan(regs, source);
+        if ranges.is_empty() {
             debug!("There is no register to read");
-            return Ok(HashMap::new());
-        }
-        // TODO: check if we can remove that
-        if sorted_regs.len() == 1 {
-            debug!("There is only one register to read");
-            let reg = sorted_regs[0].clone();
-            return Ok(self.read_range(&vec![reg], source).await?);
         }
 
-        for (mut i, r) in sorted_regs.iter().skip(1).enumerate() {
-            i = i + 1;
-            // if the range is greater than the max request size we read this batch
-            if r.addr - sorted_regs[reg_range_start].addr > MODBUS_MAX_READ_LEN
-                || r.addr != sorted_regs[reg_range_end].addr + sorted_regs[reg_range_end].len
-            {
-                let read_regs_map = self
-                    .read_range(&sorted_regs[reg_range_start..reg_range_end + 1], source)
-                    .await?;
-                // merge it with the result
-                result.extend(read_regs_map);
-
-                // reset range
-                reg_range_start = i;
+        if let (Some(pipeline), true) = (self.pipeline.clone(), ranges.len() > 1) {
+            let timeout = self.device.timeouts().response;
+            let res =
+                read_ranges_pipelined(&pipeline, &ranges, source, self.endianness, timeout).await;
+            self.last_transaction = Some(Instant::now());
+            match res {
+                // read again one range at a time once reconnected
+                Err(err) if self.should_reconnect(&err) => {
+                    warn!("Connection to the device lost ({err}), reconnecting");
+                    self.reconnect().await?;
+                }
+                // the requests given up may have been partially sent, the connection can't be used anymore
+                Err(err) if err.is_connection_error() => {
+                    self.drop_connection();
+                    return Err(err);
+                }
+                res => return res,
             }
-            reg_range_end = i;
         }
-        // read the last batch
-        let read_regs_map = self
-            .read_range(&sorted_regs[reg_range_start..reg_range_end + 1], source)
-            .await?;
-        result.extend(read_regs_map);
 
-        return Ok(result);
-    }
-    async fn read_register(
-        &mut self,
-        reg: &Register,
-        source: &ModBusRegisters,
-    ) -> Result<RegisterValue, ModbusError> {
-        let res = self.read_registers(&[reg.clone()], source).await?;
-
-        let val = res.get(&reg.name).ok_or(ModbusError::ConversionError)?;
-
-        Ok(val.clone())
+        let mut result: HashMap<String, RegisterResult> = HashMap::new();
+        for range in ranges {
+            let read_regs_map = self.read_range(&range, source).await?;
+            // merge it with the result
+            result.extend(read_regs_map);
+        }
+        Ok(result)
     }
-    async fn read_registers_by_name(
+    async fn read_registers_by_name_detailed(
         &mut self,
         names: &[String],
         source: &ModBusRegisters,
-    ) -> Result<HashMap<std::string::String, RegisterValue>, ModbusError> {
+    ) -> Result<HashMap<String, RegisterResult>, ModbusError> {
+        let mut unknown: HashMap<String, RegisterResult> = HashMap::new();
         let registers_to_read: Vec<Register> = names
             .iter()
             .filter_map(|n| {
-                let reg = match source {
-                    ModBusRegisters::INPUT => self.get_input_register_by_name(n),
-                    ModBusRegisters::HOLDING => self.get_holding_register_by_name(n),
-                };
+                let reg = self.get_table(source).get(n).cloned();
                 if reg.is_none() {
-                    warn!("Register {n} does not exist, skipping it");
+                    unknown.insert(
+                        n.to_owned(),
+                        Err(RegisterError::UnknownRegister { name: n.to_owned() }),
+                    );
                 }
                 reg
             })
             .collect();
 
-        self.read_registers(&registers_to_read, source).await
+        let mut result = self
+            .read_registers_detailed(&registers_to_read, source)
+            .await?;
+        result.extend(unknown);
+        Ok(result)
     }
-    async fn dump_registers(
+    async fn dump_registers_detailed(
         &mut self,
         source: &ModBusRegisters,
-    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
-        let registers = match source {
-            ModBusRegisters::INPUT => &self.input_registers,
-            ModBusRegisters::HOLDING => &self.holding_registers,
-        };
+    ) -> Result<HashMap<String, RegisterResult>, ModbusError> {
+        let registers = self.get_table(source);
 
         let filtered_regs: Vec<Register> = registers
             .clone()
@@ -254,7 +738,39 @@ impl ModbusConnexionAsync for ModbusDeviceAsync {
             })
             .collect();
 
-        self.read_registers(&filtered_regs, source).await
+        self.read_registers_detailed(&filtered_regs, source).await
+    }
+
+    async fn read_registers(
+        &mut self,
+        regs: &[Register],
+        source: &ModBusRegisters,
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        read_values(self.read_registers_detailed(regs, source).await?)
+    }
+    async fn read_register(
+        &mut self,
+        reg: &Register,
+        source: &ModBusRegisters,
+    ) -> Result<RegisterValue, ModbusError> {
+        let mut res = self.read_registers_detailed(&[reg.clone()], source).await?;
+
+        let val = res.remove(&reg.name).ok_or(ModbusError::ConversionError)?;
+
+        Ok(val?)
+    }
+    async fn read_registers_by_name(
+        &mut self,
+        names: &[String],
+        source: &ModBusRegisters,
+    ) -> Result<HashMap<std::string::String, RegisterValue>, ModbusError> {
+        read_values(self.read_registers_by_name_detailed(names, source).await?)
+    }
+    async fn dump_registers(
+        &mut self,
+        source: &ModBusRegisters,
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        read_values(self.dump_registers_detailed(source).await?)
     }
 
     async fn read_input_registers_by_name(
@@ -305,9 +821,28 @@ impl ModbusConnexionAsync for ModbusDeviceAsync {
         reg: &Register,
         val: &RegisterValue,
     ) -> Result<(), ModbusError> {
-        let data: Vec<u16> = val.clone().try_into()?;
+        let data: Vec<u16> = reg.encode(val, self.endianness)?;
 
-        self.write_raw_holding_registers(&reg.addr, &data).await
+        let Some(mask) = reg.mask else {
+            return self.write_words(reg, reg.addr, &data).await;
+        };
+        // the other bits of the word are kept
+        match self.bit_write_mode {
+            BitWriteMode::ReadModifyWrite => {
+                let current = self
+                    .read_raw_registers(&reg.addr, &1, &ModBusRegisters::HOLDING)
+                    .await?;
+                let current = current
+                    .first()
+                    .ok_or(ModbusError::UnexpectedResponseError)?;
+                self.write_words(reg, reg.addr, &[(current & !mask) | data[0]])
+                    .await
+            }
+            BitWriteMode::MaskWrite => {
+                self.mask_write_raw_register(&reg.addr, !mask, data[0])
+                    .await
+            }
+        }
     }
     async fn write_holding_register_by_name(
         &mut self,
@@ -322,10 +857,160 @@ impl ModbusConnexionAsync for ModbusDeviceAsync {
         self.write_holding_register(&reg, val).await
     }
 
+    async fn mask_write_holding_register(
+        &mut self,
+        reg: &Register,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), ModbusError> {
+        self.mask_write_raw_register(&reg.addr, and_mask, or_mask)
+            .await
+    }
+    async fn mask_write_holding_register_by_name(
+        &mut self,
+        name: &str,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), ModbusError> {
+        let reg = self.get_holding_register_by_name(name).ok_or(
+            ModbusError::RegisterDoesNotExistError {
+                name: name.to_string(),
+            },
+        )?;
+        self.mask_write_holding_register(&reg, and_mask, or_mask)
+            .await
+    }
+    // the registers read must be in a single range
+    async fn read_write_holding_registers(
+        &mut self,
+        write_reg: &Register,
+        val: &RegisterValue,
+        read_regs: &[Register],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        // the other bits of the word would be overwritten
+        if write_reg.mask.is_some() {
+            return Err(ModbusError::ConversionError);
+        }
+        let data: Vec<u16> = write_reg.encode(val, self.endianness)?;
+
+        let (Some(start), Some(end)) = (
+            read_regs.iter().map(|r| r.addr).min(),
+            read_regs.iter().map(|r| r.addr + r.len).max(),
+        ) else {
+            self.write_words(write_reg, write_reg.addr, &data).await?;
+            return Ok(HashMap::new());
+        };
+        let read_regs_raw = self
+            .read_write_raw_registers(&start, &(end - start), &write_reg.addr, &data)
+            .await?;
+
+        read_regs
+            .iter()
+            .map(|r| {
+                let off: usize = (r.addr - start).into();
+                let raw = read_regs_raw
+                    .get(off..off + usize::from(r.len))
+                    .ok_or(ModbusError::UnexpectedResponseError)?;
+                Ok((r.name.to_owned(), r.decode(raw.to_vec(), self.endianness)?))
+            })
+            .collect()
+    }
+    async fn read_write_holding_registers_by_name(
+        &mut self,
+        write_name: &str,
+        val: &RegisterValue,
+        read_names: &[String],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        let write_reg = self.get_holding_register_by_name(write_name).ok_or(
+            ModbusError::RegisterDoesNotExistError {
+                name: write_name.to_string(),
+            },
+        )?;
+        let read_regs = read_names
+            .iter()
+            .map(|n| {
+                self.get_holding_register_by_name(n)
+                    .ok_or(ModbusError::RegisterDoesNotExistError { name: n.to_owned() })
+            })
+            .collect::<Result<Vec<Register>, ModbusError>>()?;
+        self.read_write_holding_registers(&write_reg, val, &read_regs)
+            .await
+    }
+
+    async fn read_coils_by_name(
+        &mut self,
+        names: &[String],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        self.read_registers_by_name(names, &ModBusRegisters::COIL)
+            .await
+    }
+    async fn read_coils(
+        &mut self,
+        regs: &[Register],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        self.read_registers(regs, &ModBusRegisters::COIL).await
+    }
+    async fn dump_coils(&mut self) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        self.dump_registers(&ModBusRegisters::COIL).await
+    }
+    async fn write_coil(&mut self, reg: &Register, val: &RegisterValue) -> Result<(), ModbusError> {
+        let coil = match val {
+            RegisterValue::Boolean(val) => *val,
+            _ => return Err(ModbusError::ConversionError),
+        };
+
+        self.write_raw_coils(&reg.addr, &[coil]).await
+    }
+    async fn write_coil_by_name(
+        &mut self,
+        name: &str,
+        val: &RegisterValue,
+    ) -> Result<(), ModbusError> {
+        let reg = self
+            .get_coil_by_name(name)
+            .ok_or(ModbusError::RegisterDoesNotExistError {
+                name: name.to_string(),
+            })?;
+        self.write_coil(&reg, val).await
+    }
+
+    async fn read_discrete_inputs_by_name(
+        &mut self,
+        names: &[String],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        self.read_registers_by_name(names, &ModBusRegisters::DISCRETE)
+            .await
+    }
+    async fn read_discrete_inputs(
+        &mut self,
+        regs: &[Register],
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        self.read_registers(regs, &ModBusRegisters::DISCRETE).await
+    }
+    async fn dump_discrete_inputs(
+        &mut self,
+    ) -> Result<HashMap<String, RegisterValue>, ModbusError> {
+        self.dump_registers(&ModBusRegisters::DISCRETE).await
+    }
+
+    // following requests are sent to this slave, this is kept on reconnection
+    fn set_slave(&mut self, slave: Slave) {
+        self.device.set_slave(slave);
+        if let Some(ctx) = self.ctx.as_mut() {
+            ctx.set_slave(slave);
+        }
+    }
+
     fn get_holding_register_by_name(&mut self, name: &str) -> Option<Register> {
         self.holding_registers.get(name).cloned()
     }
     fn get_input_register_by_name(&mut self, name: &str) -> Option<Register> {
         self.input_registers.get(name).cloned()
     }
+    fn get_coil_by_name(&mut self, name: &str) -> Option<Register> {
+        self.coils.get(name).cloned()
+    }
+    fn get_discrete_input_by_name(&mut self, name: &str) -> Option<Register> {
+        self.discrete_inputs.get(name).cloned()
+    }
 }
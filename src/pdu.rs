@@ -0,0 +1,406 @@
+use tokio_modbus::{Address, Exception, Quantity};
+
+use crate::errors::ModbusError;
+
+// requests sent to the devices, independent of the transport used
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    ReadCoils(Address, Quantity),
+    ReadDiscreteInputs(Address, Quantity),
+    ReadInputRegisters(Address, Quantity),
+    ReadHoldingRegisters(Address, Quantity),
+    WriteSingleCoil(Address, bool),
+    WriteMultipleCoils(Address, Vec<bool>),
+    WriteSingleRegister(Address, u16),
+    WriteMultipleRegisters(Address, Vec<u16>),
+    MaskWriteRegister(Address, u16, u16), // and mask, or mask
+    ReadWriteMultipleRegisters(Address, Quantity, Address, Vec<u16>), // the write is done before the read
+    ReadDeviceIdentification(u8, u8), // read device id code, first object id
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    ReadCoils(Vec<bool>),
+    ReadDiscreteInputs(Vec<bool>),
+    ReadInputRegisters(Vec<u16>),
+    ReadHoldingRegisters(Vec<u16>),
+    WriteSingleCoil(Address, bool),
+    WriteMultipleCoils(Address, Quantity),
+    WriteSingleRegister(Address, u16),
+    WriteMultipleRegisters(Address, Quantity),
+    MaskWriteRegister(Address, u16, u16),
+    ReadWriteMultipleRegisters(Vec<u16>),
+    ReadDeviceIdentification(DeviceIdentificationPart),
+}
+
+// objects of a read device identification response, a device may need several requests to send all of them
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceIdentificationPart {
+    pub code: u8,
+    pub conformity_level: u8,
+    pub more_follows: bool,
+    pub next_object_id: u8,
+    pub objects: Vec<(u8, Vec<u8>)>,
+}
+
+// modbus encapsulated interface type of the read device identification requests
+pub const MEI_READ_DEVICE_ID: u8 = 0x0E;
+
+impl DeviceIdentificationPart {
+    // decode the data of the response (following the function code)
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let [MEI_READ_DEVICE_ID, code, conformity_level, more_follows, next_object_id, nb, objects @ ..] =
+            data
+        else {
+            return None;
+        };
+        let mut part = DeviceIdentificationPart {
+            code: *code,
+            conformity_level: *conformity_level,
+            more_follows: *more_follows == 0xFF,
+            next_object_id: *next_object_id,
+            objects: Vec::new(),
+        };
+        let mut rest = objects;
+        for _ in 0..*nb {
+            let [id, len, tail @ ..] = rest else {
+                return None;
+            };
+            let value = tail.get(..usize::from(*len))?;
+            part.objects.push((*id, value.to_vec()));
+            rest = &tail[usize::from(*len)..];
+        }
+        Some(part)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = vec![
+            MEI_READ_DEVICE_ID,
+            self.code,
+            self.conformity_level,
+            if self.more_follows { 0xFF } else { 0x00 },
+            self.next_object_id,
+            self.objects.len() as u8,
+        ];
+        for (id, value) in &self.objects {
+            data.extend([*id, value.len() as u8]);
+            data.extend(value);
+        }
+        data
+    }
+}
+
+impl Request {
+    // the request does not change the state of the device and can be sent again
+    pub fn is_read(&self) -> bool {
+        matches!(
+            self,
+            Request::ReadCoils(..)
+                | Request::ReadDiscreteInputs(..)
+                | Request::ReadInputRegisters(..)
+                | Request::ReadHoldingRegisters(..)
+        )
+    }
+}
+
+// exception codes returned by a server
+pub const ILLEGAL_FUNCTION: u8 = 0x01;
+pub const ILLEGAL_DATA_ADDRESS: u8 = 0x02;
+pub const ILLEGAL_DATA_VALUE: u8 = 0x03;
+
+// maximum quantities of a request, limited by the size of the protocol data unit
+pub const MAX_READ_REGISTERS: usize = 125;
+pub const MAX_READ_BITS: usize = 2000;
+pub const MAX_WRITE_REGISTERS: usize = 123;
+pub const MAX_WRITE_BITS: usize = 1968;
+pub const MAX_READ_WRITE_REGISTERS: usize = 121; // written by read/write multiple registers
+
+fn word(pdu: &[u8], idx: usize) -> Result<u16, u8> {
+    match pdu.get(idx..idx + 2) {
+        Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]])),
+        None => Err(ILLEGAL_DATA_VALUE),
+    }
+}
+
+// bits are packed LSB first, prefixed by the number of bytes
+fn pack_bits(bits: &[bool]) -> Vec<u8> {
+    let mut bytes = vec![0u8; bits.len().div_ceil(8)];
+    for (i, bit) in bits.iter().enumerate() {
+        if *bit {
+            bytes[i / 8] |= 1 << (i % 8);
+        }
+    }
+    // longer data is rejected by Request::check_quantity before being sent
+    [vec![u8::try_from(bytes.len()).unwrap_or(u8::MAX)], bytes].concat()
+}
+
+// fixed size fields, without byte count
+fn pack_fields(fields: &[u16]) -> Vec<u8> {
+    fields.iter().flat_map(|f| f.to_be_bytes()).collect()
+}
+
+// data prefixed by its number of bytes, failing if it is not complete
+fn unpack(pdu: &[u8]) -> Result<&[u8], ModbusError> {
+    let nb: usize = (*pdu.get(1).ok_or(ModbusError::UnexpectedResponseError)?).into();
+    pdu.get(2..2 + nb)
+        .ok_or(ModbusError::UnexpectedResponseError)
+}
+
+fn unpack_bits(pdu: &[u8], nb: Quantity) -> Result<Vec<bool>, ModbusError> {
+    let bytes = unpack(pdu)?;
+    if bytes.len() * 8 < usize::from(nb) {
+        return Err(ModbusError::UnexpectedResponseError);
+    }
+    Ok((0..usize::from(nb))
+        .map(|i| (bytes[i / 8] >> (i % 8)) & 1 == 1)
+        .collect())
+}
+
+fn unpack_words(pdu: &[u8]) -> Result<Vec<u16>, ModbusError> {
+    Ok(unpack(pdu)?
+        .chunks_exact(2)
+        .map(|w| u16::from_be_bytes([w[0], w[1]]))
+        .collect())
+}
+
+fn exception(code: u8) -> Option<Exception> {
+    Some(match code {
+        0x01 => Exception::IllegalFunction,
+        0x02 => Exception::IllegalDataAddress,
+        0x03 => Exception::IllegalDataValue,
+        0x04 => Exception::ServerDeviceFailure,
+        0x05 => Exception::Acknowledge,
+        0x06 => Exception::ServerDeviceBusy,
+        0x08 => Exception::MemoryParityError,
+        0x0A => Exception::GatewayPathUnavailable,
+        0x0B => Exception::GatewayTargetDevice,
+        _ => return None,
+    })
+}
+
+fn pack_words(words: &[u16]) -> Vec<u8> {
+    let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+    // longer data is rejected by Request::check_quantity before being sent
+    [vec![u8::try_from(bytes.len()).unwrap_or(u8::MAX)], bytes].concat()
+}
+
+impl Request {
+    // the quantity of values of the request fits in a protocol data unit, the exception code to send back otherwise
+    pub fn check_quantity(&self) -> Result<(), u8> {
+        let in_range = |nb: usize, max: usize| match (1..=max).contains(&nb) {
+            true => Ok(()),
+            false => Err(ILLEGAL_DATA_VALUE),
+        };
+        match self {
+            Request::ReadCoils(_, nb) | Request::ReadDiscreteInputs(_, nb) => {
+                in_range(usize::from(*nb), MAX_READ_BITS)
+            }
+            Request::ReadInputRegisters(_, nb) | Request::ReadHoldingRegisters(_, nb) => {
+                in_range(usize::from(*nb), MAX_READ_REGISTERS)
+            }
+            Request::WriteMultipleCoils(_, coils) => in_range(coils.len(), MAX_WRITE_BITS),
+            Request::WriteMultipleRegisters(_, data) => in_range(data.len(), MAX_WRITE_REGISTERS),
+            Request::ReadWriteMultipleRegisters(_, nb, _, data) => {
+                in_range(usize::from(*nb), MAX_READ_REGISTERS)?;
+                in_range(data.len(), MAX_READ_WRITE_REGISTERS)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn function(&self) -> u8 {
+        match self {
+            Request::ReadCoils(..) => 0x01,
+            Request::ReadDiscreteInputs(..) => 0x02,
+            Request::ReadHoldingRegisters(..) => 0x03,
+            Request::ReadInputRegisters(..) => 0x04,
+            Request::WriteSingleCoil(..) => 0x05,
+            Request::WriteSingleRegister(..) => 0x06,
+            Request::WriteMultipleCoils(..) => 0x0F,
+            Request::WriteMultipleRegisters(..) => 0x10,
+            Request::MaskWriteRegister(..) => 0x16,
+            Request::ReadWriteMultipleRegisters(..) => 0x17,
+            Request::ReadDeviceIdentification(..) => 0x2B,
+        }
+    }
+
+    // protocol data unit of the request (function code followed by its data)
+    pub fn encode(&self) -> Vec<u8> {
+        let data = match self {
+            Request::ReadCoils(addr, nb)
+            | Request::ReadDiscreteInputs(addr, nb)
+            | Request::ReadHoldingRegisters(addr, nb)
+            | Request::ReadInputRegisters(addr, nb) => pack_fields(&[*addr, *nb]),
+            Request::WriteSingleCoil(addr, coil) => {
+                pack_fields(&[*addr, if *coil { 0xFF00 } else { 0x0000 }])
+            }
+            Request::WriteSingleRegister(addr, value) => pack_fields(&[*addr, *value]),
+            Request::WriteMultipleCoils(addr, coils) => {
+                [pack_fields(&[*addr, coils.len() as u16]), pack_bits(coils)].concat()
+            }
+            Request::WriteMultipleRegisters(addr, data) => {
+                [pack_fields(&[*addr, data.len() as u16]), pack_words(data)].concat()
+            }
+            Request::MaskWriteRegister(addr, and_mask, or_mask) => {
+                pack_fields(&[*addr, *and_mask, *or_mask])
+            }
+            Request::ReadWriteMultipleRegisters(read_addr, nb, write_addr, data) => [
+                pack_fields(&[*read_addr, *nb, *write_addr, data.len() as u16]),
+                pack_words(data),
+            ]
+            .concat(),
+            Request::ReadDeviceIdentification(code, object_id) => {
+                vec![MEI_READ_DEVICE_ID, *code, *object_id]
+            }
+        };
+        [vec![self.function()], data].concat()
+    }
+
+    // decode the protocol data unit of a request (function code followed by its data),
+    // failing with the exception code to send back
+    pub fn decode(pdu: &[u8]) -> Result<Request, u8> {
+        let function = *pdu.first().ok_or(ILLEGAL_FUNCTION)?;
+        let request = match function {
+            0x01 => Request::ReadCoils(word(pdu, 1)?, word(pdu, 3)?),
+            0x02 => Request::ReadDiscreteInputs(word(pdu, 1)?, word(pdu, 3)?),
+            0x03 => Request::ReadHoldingRegisters(word(pdu, 1)?, word(pdu, 3)?),
+            0x04 => Request::ReadInputRegisters(word(pdu, 1)?, word(pdu, 3)?),
+            0x05 => {
+                let coil = match word(pdu, 3)? {
+                    0xFF00 => true,
+                    0x0000 => false,
+                    _ => return Err(ILLEGAL_DATA_VALUE),
+                };
+                Request::WriteSingleCoil(word(pdu, 1)?, coil)
+            }
+            0x06 => Request::WriteSingleRegister(word(pdu, 1)?, word(pdu, 3)?),
+            0x0F => {
+                let nb: usize = word(pdu, 3)?.into();
+                let bytes = pdu.get(6..).ok_or(ILLEGAL_DATA_VALUE)?;
+                if bytes.len() * 8 < nb {
+                    return Err(ILLEGAL_DATA_VALUE);
+                }
+                let coils = (0..nb).map(|i| (bytes[i / 8] >> (i % 8)) & 1 == 1);
+                Request::WriteMultipleCoils(word(pdu, 1)?, coils.collect())
+            }
+            0x10 => {
+                let nb: usize = word(pdu, 3)?.into();
+                let data = (0..nb).map(|i| word(pdu, 6 + 2 * i));
+                Request::WriteMultipleRegisters(word(pdu, 1)?, data.collect::<Result<_, _>>()?)
+            }
+            0x16 => Request::MaskWriteRegister(word(pdu, 1)?, word(pdu, 3)?, word(pdu, 5)?),
+            0x2B => match pdu.get(1..4) {
+                Some([MEI_READ_DEVICE_ID, code, object_id]) => {
+                    Request::ReadDeviceIdentification(*code, *object_id)
+                }
+                _ => return Err(ILLEGAL_FUNCTION),
+            },
+            0x17 => {
+                let nb: usize = word(pdu, 7)?.into();
+                let data = (0..nb).map(|i| word(pdu, 10 + 2 * i));
+                Request::ReadWriteMultipleRegisters(
+                    word(pdu, 1)?,
+                    word(pdu, 3)?,
+                    word(pdu, 5)?,
+                    data.collect::<Result<_, _>>()?,
+                )
+            }
+            _ => return Err(ILLEGAL_FUNCTION),
+        };
+        request.check_quantity()?;
+        Ok(request)
+    }
+}
+
+impl Response {
+    // decode the protocol data unit of the response to a request, exception responses are returned as errors
+    pub fn decode(request: &Request, pdu: &[u8]) -> Result<Response, ModbusError> {
+        let function = request.function();
+        let field = |idx| word(pdu, idx).map_err(|_| ModbusError::UnexpectedResponseError);
+        match pdu.first() {
+            Some(code) if *code == function => {}
+            Some(code) if *code == function | 0x80 => {
+                let err = pdu.get(1).and_then(|code| exception(*code));
+                return Err(err.ok_or(ModbusError::UnexpectedResponseError)?.into());
+            }
+            _ => return Err(ModbusError::UnexpectedResponseError),
+        }
+        Ok(match request {
+            Request::ReadCoils(_, nb) => Response::ReadCoils(unpack_bits(pdu, *nb)?),
+            Request::ReadDiscreteInputs(_, nb) => {
+                Response::ReadDiscreteInputs(unpack_bits(pdu, *nb)?)
+            }
+            Request::ReadHoldingRegisters(..) => Response::ReadHoldingRegisters(unpack_words(pdu)?),
+            Request::ReadInputRegisters(..) => Response::ReadInputRegisters(unpack_words(pdu)?),
+            Request::WriteSingleCoil(..) => {
+                Response::WriteSingleCoil(field(1)?, field(3)? == 0xFF00)
+            }
+            Request::WriteSingleRegister(..) => Response::WriteSingleRegister(field(1)?, field(3)?),
+            Request::WriteMultipleCoils(..) => Response::WriteMultipleCoils(field(1)?, field(3)?),
+            Request::WriteMultipleRegisters(..) => {
+                Response::WriteMultipleRegisters(field(1)?, field(3)?)
+            }
+            Request::MaskWriteRegister(..) => {
+                Response::MaskWriteRegister(field(1)?, field(3)?, field(5)?)
+            }
+            Request::ReadWriteMultipleRegisters(..) => {
+                Response::ReadWriteMultipleRegisters(unpack_words(pdu)?)
+            }
+            Request::ReadDeviceIdentification(..) => Response::ReadDeviceIdentification(
+                DeviceIdentificationPart::decode(&pdu[1..])
+                    .ok_or(ModbusError::UnexpectedResponseError)?,
+            ),
+        })
+    }
+
+    // protocol data unit of the response (function code followed by its data)
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Response::ReadCoils(bits) => [vec![0x01], pack_bits(bits)].concat(),
+            Response::ReadDiscreteInputs(bits) => [vec![0x02], pack_bits(bits)].concat(),
+            Response::ReadHoldingRegisters(words) => [vec![0x03], pack_words(words)].concat(),
+            Response::ReadInputRegisters(words) => [vec![0x04], pack_words(words)].concat(),
+            Response::WriteSingleCoil(addr, coil) => {
+                let value: u16 = if *coil { 0xFF00 } else { 0x0000 };
+                [
+                    vec![0x05],
+                    addr.to_be_bytes().to_vec(),
+                    value.to_be_bytes().to_vec(),
+                ]
+                .concat()
+            }
+            Response::WriteSingleRegister(addr, value) => [
+                vec![0x06],
+                addr.to_be_bytes().to_vec(),
+                value.to_be_bytes().to_vec(),
+            ]
+            .concat(),
+            Response::WriteMultipleCoils(addr, nb) => [
+                vec![0x0F],
+                addr.to_be_bytes().to_vec(),
+                nb.to_be_bytes().to_vec(),
+            ]
+            .concat(),
+            Response::WriteMultipleRegisters(addr, nb) => [
+                vec![0x10],
+                addr.to_be_bytes().to_vec(),
+                nb.to_be_bytes().to_vec(),
+            ]
+            .concat(),
+            Response::ReadDeviceIdentification(part) => [vec![0x2B], part.encode()].concat(),
+            Response::ReadWriteMultipleRegisters(words) => [vec![0x17], pack_words(words)].concat(),
+            Response::MaskWriteRegister(addr, and_mask, or_mask) => [
+                vec![0x16],
+                addr.to_be_bytes().to_vec(),
+                and_mask.to_be_bytes().to_vec(),
+                or_mask.to_be_bytes().to_vec(),
+            ]
+            .concat(),
+        }
+    }
+}
+
+// protocol data unit of an exception response
+pub fn encode_exception(function: u8, code: u8) -> Vec<u8> {
+    vec![function | 0x80, code]
+}
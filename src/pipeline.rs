@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU16, AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    sync::{oneshot, Semaphore},
+    task::JoinHandle,
+};
+use tokio_modbus::Slave;
+
+use crate::{
+    errors::ModbusError,
+    pdu::{Request, Response},
+    transport::Transport,
+};
+
+// requests waiting for their response by transaction id, none once the connection is closed
+type Pending = Arc<Mutex<Option<HashMap<u16, oneshot::Sender<Vec<u8>>>>>>;
+
+fn connection_closed() -> ModbusError {
+    std::io::Error::from(std::io::ErrorKind::ConnectionAborted).into()
+}
+
+// stops reading the responses once every clone of the connection is dropped
+#[derive(Debug)]
+struct ReaderTask(JoinHandle<()>);
+
+impl Drop for ReaderTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+// a request given up before its response is received no longer waits for it
+struct PendingGuard<'a> {
+    pending: &'a Pending,
+    id: u16,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(pending) = self.pending.lock().unwrap().as_mut() {
+            pending.remove(&self.id);
+        }
+    }
+}
+
+async fn read_responses(mut reader: OwnedReadHalf, pending: Pending) {
+    loop {
+        // transaction id, protocol id, length and unit id
+        let mut header = [0u8; 7];
+        if let Err(err) = reader.read_exact(&mut header).await {
+            debug!("Pipelined connection closed ({err})");
+            break;
+        }
+        let len: usize = u16::from_be_bytes([header[4], header[5]]).into();
+        let mut pdu = vec![0u8; len.saturating_sub(1)];
+        if let Err(err) = reader.read_exact(&mut pdu).await {
+            debug!("Pipelined connection closed ({err})");
+            break;
+        }
+
+        let id = u16::from_be_bytes([header[0], header[1]]);
+        let waiting = pending.lock().unwrap().as_mut().and_then(|p| p.remove(&id));
+        match waiting {
+            Some(tx) => {
+                let _ = tx.send(pdu);
+            }
+            None => warn!("Received a response to the unknown transaction {id}, dropping it"),
+        }
+    }
+    // the requests still waiting fail
+    pending.lock().unwrap().take();
+}
+
+// Modbus TCP connection sending several requests without waiting for the previous responses,
+// the responses are matched to their request by transaction id
+#[derive(Debug, Clone)]
+pub struct PipelinedTcp {
+    writer: Arc<tokio::sync::Mutex<OwnedWriteHalf>>,
+    pending: Pending,
+    window: Arc<Semaphore>, // number of requests in flight
+    next_id: Arc<AtomicU16>,
+    slave: Arc<AtomicU8>, // shared by the clones, changing it applies to all of them
+    _reader: Arc<ReaderTask>,
+}
+
+impl PipelinedTcp {
+    pub async fn connect(
+        addr: SocketAddr,
+        slave: Slave,
+        window: usize,
+    ) -> Result<Self, ModbusError> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+
+        let pending: Pending = Arc::new(Mutex::new(Some(HashMap::new())));
+        let reader = tokio::spawn(read_responses(reader, pending.clone()));
+        Ok(PipelinedTcp {
+            writer: Arc::new(tokio::sync::Mutex::new(writer)),
+            pending,
+            window: Arc::new(Semaphore::new(window.max(1))),
+            next_id: Arc::new(AtomicU16::new(0)),
+            slave: Arc::new(AtomicU8::new(slave.0)),
+            _reader: Arc::new(ReaderTask(reader)),
+        })
+    }
+
+    // can be called concurrently from clones of the connection
+    pub async fn send(&self, request: &Request) -> Result<Response, ModbusError> {
+        let _permit = self
+            .window
+            .acquire()
+            .await
+            .map_err(|_| connection_closed())?;
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        match self.pending.lock().unwrap().as_mut() {
+            Some(pending) => pending.insert(id, tx),
+            None => return Err(connection_closed()),
+        };
+        let _guard = PendingGuard {
+            pending: &self.pending,
+            id,
+        };
+
+        let pdu = request.encode();
+        let mut frame = Vec::with_capacity(pdu.len() + 7);
+        frame.extend(id.to_be_bytes());
+        frame.extend([0, 0]);
+        frame.extend((pdu.len() as u16 + 1).to_be_bytes());
+        frame.push(self.slave.load(Ordering::Relaxed));
+        frame.extend(pdu);
+        self.writer.lock().await.write_all(&frame).await?;
+
+        let pdu = rx.await.map_err(|_| connection_closed())?;
+        Response::decode(request, &pdu)
+    }
+}
+
+#[async_trait]
+impl Transport for PipelinedTcp {
+    async fn call(&mut self, request: Request) -> Result<Response, ModbusError> {
+        self.send(&request).await
+    }
+
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave.store(slave.0, Ordering::Relaxed);
+    }
+}
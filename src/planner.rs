@@ -0,0 +1,87 @@
+use std::ops::Range;
+
+use tokio_modbus::Address;
+
+use crate::{
+    register::Register, types::ModBusRegisters, MODBUS_MAX_READ_BITS, MODBUS_MAX_READ_LEN,
+};
+
+// policy used to group the registers to read into requests
+#[derive(Debug, Clone)]
+pub struct ReadPlanner {
+    pub max_gap: u16, // number of unused addresses that can be read to merge two ranges
+    pub max_read_len: u16, // registers read at once, capped by the protocol
+    pub max_read_bits: u16, // coils or discrete inputs read at once, capped by the protocol
+    pub forbidden: Vec<(ModBusRegisters, Range<Address>)>, // addresses the device refuses to read
+}
+
+impl Default for ReadPlanner {
+    // only the contiguous registers are merged
+    fn default() -> Self {
+        ReadPlanner {
+            max_gap: 0,
+            max_read_len: MODBUS_MAX_READ_LEN,
+            max_read_bits: MODBUS_MAX_READ_BITS,
+            forbidden: Vec::new(),
+        }
+    }
+}
+
+impl ReadPlanner {
+    fn max_len(&self, source: &ModBusRegisters) -> u32 {
+        let len = match source {
+            ModBusRegisters::COIL | ModBusRegisters::DISCRETE => {
+                self.max_read_bits.min(MODBUS_MAX_READ_BITS)
+            }
+            ModBusRegisters::INPUT | ModBusRegisters::HOLDING => {
+                self.max_read_len.min(MODBUS_MAX_READ_LEN)
+            }
+        };
+        len.max(1).into()
+    }
+
+    fn is_forbidden(&self, source: &ModBusRegisters, gap: Range<u32>) -> bool {
+        !gap.is_empty()
+            && self.forbidden.iter().any(|(table, range)| {
+                table == source
+                    && u32::from(range.start) < gap.end
+                    && gap.start < u32::from(range.end)
+            })
+    }
+
+    // group the registers into the ranges to read, in order of address
+    // a register longer than the maximum read length is read alone
+    pub fn plan(&self, regs: &[Register], source: &ModBusRegisters) -> Vec<Vec<Register>> {
+        let mut sorted_regs = regs.to_vec();
+        sorted_regs.sort_by_key(|s| s.addr);
+
+        let max_len = self.max_len(source);
+        let mut ranges: Vec<Vec<Register>> = Vec::new();
+        // first and next unread address of the current range
+        let mut start: u32 = 0;
+        let mut end: u32 = 0;
+
+        for reg in sorted_regs {
+            let reg_start = u32::from(reg.addr);
+            let reg_end = reg_start + u32::from(reg.len);
+
+            let mergeable = !ranges.is_empty()
+                && reg_start <= end + u32::from(self.max_gap)
+                && reg_end.max(end) - start <= max_len
+                && !self.is_forbidden(source, end..reg_start);
+
+            match ranges.last_mut() {
+                Some(range) if mergeable => {
+                    range.push(reg);
+                    end = end.max(reg_end);
+                }
+                _ => {
+                    ranges.push(vec![reg]);
+                    start = reg_start;
+                    end = reg_end;
+                }
+            }
+        }
+        ranges
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use log::{debug, warn};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::Instant,
+};
+
+use crate::{
+    deadband::{ChangeFilter, Deadband},
+    errors::ModbusError,
+    modbus_connexion_async::ModbusConnexionAsync,
+    types::{ModBusRegisters, RegisterResult, RegisterValue},
+    ModbusDeviceAsync,
+};
+
+// number of results that can wait in the channel before the poller stops reading
+const CHANNEL_CAPACITY: usize = 64;
+
+// registers of a table read together at the same interval
+#[derive(Debug, Clone)]
+pub struct PollGroup {
+    pub source: ModBusRegisters,
+    pub names: Vec<String>,
+    pub interval: Duration,
+}
+
+// values read from a table during a tick
+#[derive(Debug)]
+pub struct PollResult {
+    pub timestamp: SystemTime,
+    pub source: ModBusRegisters,
+    pub values: Result<HashMap<String, RegisterResult>, ModbusError>,
+}
+
+// new value of a register reported by a subscription
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub timestamp: SystemTime,
+    pub source: ModBusRegisters,
+    pub name: String,
+    pub value: RegisterValue,
+}
+
+// reads groups of registers periodically, the groups due at the same time are read with the same requests
+#[derive(Debug, Clone, Default)]
+pub struct Poller {
+    groups: Vec<PollGroup>,
+    // only used by the subscriptions
+    filter: ChangeFilter,
+}
+
+impl Poller {
+    pub fn new() -> Self {
+        Poller::default()
+    }
+
+    pub fn with_group(
+        mut self,
+        source: ModBusRegisters,
+        names: &[&str],
+        interval: Duration,
+    ) -> Self {
+        self.groups.push(PollGroup {
+            source,
+            names: names.iter().map(|n| n.to_string()).collect(),
+            interval,
+        });
+        self
+    }
+
+    pub fn with_input_registers(self, names: &[&str], interval: Duration) -> Self {
+        self.with_group(ModBusRegisters::INPUT, names, interval)
+    }
+
+    pub fn with_holding_registers(self, names: &[&str], interval: Duration) -> Self {
+        self.with_group(ModBusRegisters::HOLDING, names, interval)
+    }
+
+    pub fn with_coils(self, names: &[&str], interval: Duration) -> Self {
+        self.with_group(ModBusRegisters::COIL, names, interval)
+    }
+
+    pub fn with_discrete_inputs(self, names: &[&str], interval: Duration) -> Self {
+        self.with_group(ModBusRegisters::DISCRETE, names, interval)
+    }
+
+    // changes of a numeric register smaller than the deadband are not reported by the subscriptions
+    pub fn with_deadband(
+        mut self,
+        source: ModBusRegisters,
+        name: &str,
+        deadband: Deadband,
+    ) -> Self {
+        self.filter = self.filter.with_deadband(source, name, deadband);
+        self
+    }
+
+    // poll the device in a task until the handle is stopped or the receiver is dropped
+    pub fn spawn(self, device: ModbusDeviceAsync) -> (PollerHandle, mpsc::Receiver<PollResult>) {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let task = tokio::spawn(self.run(device, tx, stop_rx));
+        (
+            PollerHandle {
+                stop: stop_tx,
+                task,
+            },
+            rx,
+        )
+    }
+
+    // poll the device and report only the registers whose value changed, the first value read is always reported
+    pub fn subscribe(
+        mut self,
+        device: ModbusDeviceAsync,
+    ) -> (PollerHandle, mpsc::Receiver<Change>) {
+        let mut filter = std::mem::take(&mut self.filter);
+        let (handle, mut results) = self.spawn(device);
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            loop {
+                let res = tokio::select! {
+                    res = results.recv() => match res {
+                        Some(res) => res,
+                        None => break,
+                    },
+                    _ = tx.closed() => break,
+                };
+                let values = match res.values {
+                    Ok(values) => values,
+                    Err(err) => {
+                        warn!("Could not poll the {0:?} registers ({err})", res.source);
+                        continue;
+                    }
+                };
+                for (name, val) in values {
+                    match val {
+                        Ok(value) if filter.update(&res.source, &name, &value) => {
+                            let change = Change {
+                                timestamp: res.timestamp,
+                                source: res.source.clone(),
+                                name,
+                                value,
+                            };
+                            if tx.send(change).await.is_err() {
+                                return;
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            warn!("Could not poll register {name} ({err})");
+                            // reported again once it can be read
+                            filter.reset(&res.source, &name);
+                        }
+                    }
+                }
+            }
+        });
+        (handle, rx)
+    }
+
+    async fn run(
+        self,
+        mut device: ModbusDeviceAsync,
+        tx: mpsc::Sender<PollResult>,
+        mut stop: oneshot::Receiver<()>,
+    ) -> ModbusDeviceAsync {
+        let start = Instant::now();
+        let mut next: Vec<Instant> = vec![start; self.groups.len()];
+
+        while let Some(deadline) = next.iter().min().copied() {
+            tokio::select! {
+                _ = &mut stop => break,
+                _ = tx.closed() => break,
+                _ = tokio::time::sleep_until(deadline) => {}
+            }
+
+            let now = Instant::now();
+            let mut reads: Vec<(ModBusRegisters, Vec<String>)> = Vec::new();
+            for (group, next) in self.groups.iter().zip(next.iter_mut()) {
+                if *next > now {
+                    continue;
+                }
+                // the ticks missed because of slow reads are skipped
+                *next += group.interval;
+                if *next <= now {
+                    *next = now + group.interval;
+                }
+
+                let i = match reads.iter().position(|(source, _)| *source == group.source) {
+                    Some(i) => i,
+                    None => {
+                        reads.push((group.source.clone(), Vec::new()));
+                        reads.len() - 1
+                    }
+                };
+                let names = &mut reads[i].1;
+                for name in &group.names {
+                    if !names.contains(name) {
+                        names.push(name.to_owned());
+                    }
+                }
+            }
+
+            for (source, names) in reads {
+                debug!("polling {0} registers of {source:?}", names.len());
+                let values = device
+                    .read_registers_by_name_detailed(&names, &source)
+                    .await;
+                let result = PollResult {
+                    timestamp: SystemTime::now(),
+                    source,
+                    values,
+                };
+                if tx.send(result).await.is_err() {
+                    return device;
+                }
+            }
+        }
+        device
+    }
+}
+
+#[derive(Debug)]
+pub struct PollerHandle {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<ModbusDeviceAsync>,
+}
+
+impl PollerHandle {
+    // stop polling and get the device back
+    pub async fn stop(self) -> Result<ModbusDeviceAsync, ModbusError> {
+        let _ = self.stop.send(());
+        self.task
+            .await
+            .map_err(|err| ModbusError::IOerror { err: err.into() })
+    }
+}
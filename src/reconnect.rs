@@ -0,0 +1,59 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// policy used to re-establish a lost connection, the delay between attempts grows exponentially
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    pub max_attempts: Option<u32>, // retry forever if not set
+    pub jitter: f64,               // fraction of the delay randomly added or removed (0..1)
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            max_attempts: Some(10),
+            jitter: 0.1,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    // delay to wait before the given attempt (starting at 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let base = (self.initial_delay.as_secs_f64() * self.multiplier.powi(exp))
+            .min(self.max_delay.as_secs_f64());
+        let jitter = base * self.jitter.clamp(0.0, 1.0) * (2.0 * random_unit() - 1.0);
+
+        Duration::from_secs_f64((base + jitter).max(0.0))
+    }
+}
+
+// random number in [0, 1), good enough for jitter and avoids pulling a rng
+fn random_unit() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Disconnected,
+    Connected,
+    Reconnecting { attempt: u32 },
+    Failed,
+}
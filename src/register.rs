@@ -1,4 +1,11 @@
-use std::fmt::Debug;
+use std::{collections::HashMap, fmt::Debug};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{ModbusError, RegisterError},
+    types::RegisterValue,
+};
 
 #[derive(Debug, Copy, Clone)]
 pub enum DataType {
@@ -6,11 +13,135 @@ pub enum DataType {
     UInt32,
     UInt64,
     UInt128,
+    Int16,
     Int32,
+    Int64,
     Enum16,
     Sized,
     Float32,
+    Float64,
     Boolean,
+    String(StringFormat), // the length of the string is the length of the register
+}
+
+// order of the bytes on the wire, A being the most significant byte of the value
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Endianness {
+    #[default]
+    ABCD,
+    CDAB,
+    BADC,
+    DCBA,
+}
+
+impl Endianness {
+    // reorder big endian (ABCD) bytes to this ordering, since every ordering is its own inverse
+    // this is also used to get back big endian bytes from the wire
+    pub fn reorder(&self, bytes: &[u8]) -> Vec<u8> {
+        match self {
+            Endianness::ABCD => bytes.to_vec(),
+            Endianness::CDAB => bytes.chunks(2).rev().flatten().copied().collect(),
+            Endianness::BADC => bytes
+                .chunks(2)
+                .flat_map(|word| word.iter().rev())
+                .copied()
+                .collect(),
+            Endianness::DCBA => bytes.iter().rev().copied().collect(),
+        }
+    }
+}
+
+// function used to write the holding registers
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WriteStrategy {
+    Auto,   // write single register (FC6) for one word, write multiple registers (FC16) otherwise
+    Single, // write single register (FC6) for each word, for the devices without FC16
+    #[default]
+    Multiple, // write multiple registers (FC16)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StringEncoding {
+    Ascii,
+    #[default]
+    #[serde(alias = "utf-8")]
+    Utf8,
+    #[serde(alias = "latin-1", alias = "iso-8859-1")]
+    Latin1,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StringFormat {
+    pub encoding: StringEncoding,
+    pub byte_swap: bool, // the first character of each word is in the low byte
+    pub trim: bool,      // the string ends at the first NUL and the spaces around it are removed
+}
+
+impl Default for StringFormat {
+    fn default() -> Self {
+        StringFormat {
+            encoding: StringEncoding::default(),
+            byte_swap: false,
+            trim: true,
+        }
+    }
+}
+
+impl StringFormat {
+    pub fn decode(&self, raw: &[u16]) -> Option<String> {
+        let bytes: Vec<u8> = raw
+            .iter()
+            .flat_map(|w| match self.byte_swap {
+                true => w.to_le_bytes(),
+                false => w.to_be_bytes(),
+            })
+            .collect();
+        let bytes = match (self.trim, bytes.iter().position(|b| *b == 0)) {
+            (true, Some(end)) => &bytes[..end],
+            _ => &bytes[..],
+        };
+
+        let res: String = match self.encoding {
+            StringEncoding::Ascii if !bytes.is_ascii() => return None,
+            StringEncoding::Utf8 => String::from_utf8(bytes.to_vec()).ok()?,
+            StringEncoding::Ascii | StringEncoding::Latin1 => {
+                bytes.iter().map(|b| char::from(*b)).collect()
+            }
+        };
+        match self.trim {
+            true => Some(res.trim_matches(' ').to_string()),
+            false => Some(res),
+        }
+    }
+
+    // the string is padded with NUL to the given number of registers
+    pub fn encode(&self, val: &str, len: u16) -> Option<Vec<u16>> {
+        let mut bytes: Vec<u8> = match self.encoding {
+            StringEncoding::Ascii if !val.is_ascii() => return None,
+            StringEncoding::Ascii | StringEncoding::Utf8 => val.as_bytes().to_vec(),
+            StringEncoding::Latin1 => val
+                .chars()
+                .map(|c| u8::try_from(c).ok())
+                .collect::<Option<_>>()?,
+        };
+        let size = 2 * usize::from(len);
+        if bytes.len() > size {
+            return None;
+        }
+        bytes.resize(size, 0);
+
+        Some(
+            bytes
+                .chunks(2)
+                .map(|b| match self.byte_swap {
+                    true => u16::from_le_bytes([b[0], b[1]]),
+                    false => u16::from_be_bytes([b[0], b[1]]),
+                })
+                .collect(),
+        )
+    }
 }
 
 #[derive(Clone)]
@@ -20,6 +151,14 @@ pub struct Register {
     pub len: u16, // in 16bits
     pub data_type: DataType,
     pub read: bool,
+    pub endianness: Option<Endianness>, // use the device default if not set
+    // the value read is raw * scale + offset, returned as a float
+    pub scale: Option<f64>,
+    pub offset: Option<f64>,
+    pub unit: Option<String>,
+    pub labels: Option<HashMap<u16, String>>, // symbolic values of the enums
+    pub mask: Option<u16>, // bits of the word holding the value, the value is shifted to the lowest bit
+    pub write_strategy: Option<WriteStrategy>, // use the device default if not set
 }
 
 impl Debug for Register {
@@ -30,6 +169,126 @@ impl Debug for Register {
             .field("len", &self.len)
             .field("data_type", &self.data_type)
             .field("read", &self.read)
+            .field("endianness", &self.endianness)
+            .field("scale", &self.scale)
+            .field("offset", &self.offset)
+            .field("unit", &self.unit)
+            .field("labels", &self.labels)
+            .field("mask", &self.mask)
+            .field("write_strategy", &self.write_strategy)
             .finish()
     }
 }
+
+impl Register {
+    // readable register without the optional metadata
+    pub fn new(name: &str, addr: u16, len: u16, data_type: DataType) -> Self {
+        Register {
+            name: name.to_string(),
+            addr,
+            len,
+            data_type,
+            read: true,
+            endianness: None,
+            scale: None,
+            offset: None,
+            unit: None,
+            labels: None,
+            mask: None,
+            write_strategy: None,
+        }
+    }
+
+    pub(crate) fn is_scaled(&self) -> bool {
+        self.scale.is_some() || self.offset.is_some()
+    }
+
+    // value of the register from the words read on the device
+    pub fn decode(
+        &self,
+        raw: Vec<u16>,
+        default_endianness: Endianness,
+    ) -> Result<RegisterValue, RegisterError> {
+        let endianness = self.endianness.unwrap_or(default_endianness);
+        let words = match (self.mask, raw.first()) {
+            (Some(mask), Some(word)) => vec![(word & mask)
+                .checked_shr(mask.trailing_zeros())
+                .unwrap_or(0)],
+            _ => raw.clone(),
+        };
+        let val = RegisterValue::try_from((words, self.data_type, endianness));
+        match val {
+            Ok(val) if self.is_scaled() => match val.as_f64() {
+                Some(val) => Ok(RegisterValue::Float64(
+                    val * self.scale.unwrap_or(1.0) + self.offset.unwrap_or(0.0),
+                )),
+                None => Err(RegisterError::ConversionError { raw }),
+            },
+            Ok(RegisterValue::Enum16(val)) => {
+                match self.labels.as_ref().and_then(|labels| labels.get(&val)) {
+                    Some(label) => Ok(RegisterValue::Label(val, label.clone())),
+                    None => Ok(RegisterValue::Enum16(val)),
+                }
+            }
+            Ok(val) => Ok(val),
+            Err(_) => Err(RegisterError::ConversionError { raw }),
+        }
+    }
+
+    // words to write to the device for the value, the scaling is inverted
+    // and the labels of the enums are replaced by their value
+    // only the bits of the mask are set for the registers with a mask
+    pub fn encode(
+        &self,
+        val: &RegisterValue,
+        default_endianness: Endianness,
+    ) -> Result<Vec<u16>, ModbusError> {
+        let raw_val;
+        let val = match (val, &self.labels) {
+            (val, _) if self.is_scaled() => {
+                let scaled = val.as_f64().ok_or(ModbusError::ConversionError)?;
+                let raw = (scaled - self.offset.unwrap_or(0.0)) / self.scale.unwrap_or(1.0);
+                raw_val = RegisterValue::from_f64(raw, self.data_type)
+                    .ok_or(ModbusError::ConversionError)?;
+                &raw_val
+            }
+            (RegisterValue::String(label), Some(labels)) => {
+                let value = labels
+                    .iter()
+                    .find_map(|(value, l)| (l == label).then_some(*value))
+                    .ok_or(ModbusError::UnknownLabelError {
+                        label: label.clone(),
+                    })?;
+                raw_val = RegisterValue::Enum16(value);
+                &raw_val
+            }
+            (val, _) => val,
+        };
+        let words = match (val, self.data_type) {
+            (RegisterValue::String(val), DataType::String(format)) => format
+                .encode(val, self.len)
+                .ok_or(ModbusError::ConversionError)?,
+            (val, _) => val.to_words(self.endianness.unwrap_or(default_endianness))?,
+        };
+        // a value of another size would overwrite the next registers or only a part of this one
+        if words.len() != usize::from(self.len) {
+            return Err(ModbusError::ConversionError);
+        }
+        match self.mask {
+            Some(mask) => Ok(vec![self.to_bits(&words, mask)?]),
+            None => Ok(words),
+        }
+    }
+
+    // place the value under the mask, it must fit in the bits of the mask
+    fn to_bits(&self, words: &[u16], mask: u16) -> Result<u16, ModbusError> {
+        let [word] = words else {
+            return Err(ModbusError::ConversionError);
+        };
+        let bits = u32::from(*word) << mask.trailing_zeros();
+        match bits & !u32::from(mask) {
+            0 => Ok(bits as u16),
+            _ => Err(ModbusError::ConversionError),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_modbus::Slave;
+
+use crate::{
+    errors::ModbusError,
+    pdu::{Request, Response},
+    transport::Transport,
+};
+
+// silence between two frames above 19200 bauds, also used when the speed of the line is unknown
+pub const MIN_FRAME_GAP: Duration = Duration::from_micros(1750);
+
+// cyclic redundancy check of the RTU frames, sent low byte first
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+// slave, PDU and CRC
+pub fn encode_frame(slave: u8, pdu: &[u8]) -> Vec<u8> {
+    let mut frame = [vec![slave], pdu.to_vec()].concat();
+    let crc = crc16(&frame);
+    frame.extend_from_slice(&crc.to_le_bytes());
+    frame
+}
+
+async fn read_bytes<T>(port: &mut T, frame: &mut Vec<u8>, nb: usize) -> std::io::Result<()>
+where
+    T: AsyncRead + Unpin,
+{
+    let start = frame.len();
+    frame.resize(start + nb, 0);
+    port.read_exact(&mut frame[start..]).await?;
+    Ok(())
+}
+
+// the RTU frames are not delimited, the length of a response is read from its content
+pub(crate) async fn read_response<T>(port: &mut T) -> Result<Vec<u8>, ModbusError>
+where
+    T: AsyncRead + Unpin,
+{
+    // slave and function code
+    let mut frame = Vec::new();
+    read_bytes(port, &mut frame, 2).await?;
+    match frame[1] {
+        function if function & 0x80 != 0 => read_bytes(port, &mut frame, 1).await?,
+        0x01..=0x04 | 0x17 => {
+            read_bytes(port, &mut frame, 1).await?;
+            let count = frame[2].into();
+            read_bytes(port, &mut frame, count).await?;
+        }
+        0x05 | 0x06 | 0x0F | 0x10 => read_bytes(port, &mut frame, 4).await?,
+        0x16 => read_bytes(port, &mut frame, 6).await?,
+        0x2B => {
+            // MEI type, read device id code, conformity level, more follows, next object id and number of objects
+            read_bytes(port, &mut frame, 6).await?;
+            for _ in 0..frame[7] {
+                // object id and length
+                read_bytes(port, &mut frame, 2).await?;
+                let len = frame[frame.len() - 1].into();
+                read_bytes(port, &mut frame, len).await?;
+            }
+        }
+        function => {
+            warn!("Received a response to the unknown function {function:#04X}");
+            return Err(ModbusError::UnexpectedResponseError);
+        }
+    }
+    read_bytes(port, &mut frame, 2).await?;
+    Ok(frame)
+}
+
+// Modbus RTU on a serial line or a TCP connection to a converter
+#[derive(Debug)]
+pub struct RtuTransport<T> {
+    port: T,
+    slave: Slave,
+    frame_gap: Duration,
+}
+
+impl<T> RtuTransport<T>
+where
+    T: AsyncRead + Unpin,
+{
+    pub fn new(port: T, slave: Slave, frame_gap: Duration) -> Self {
+        RtuTransport {
+            port,
+            slave,
+            frame_gap,
+        }
+    }
+
+    // drop the bytes received until the line is silent for the frame gap
+    async fn wait_silence(&mut self) -> std::io::Result<()> {
+        let mut buf = [0u8; 256];
+        while let Ok(n) = tokio::time::timeout(self.frame_gap, self.port.read(&mut buf)).await {
+            if n? == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T> Transport for RtuTransport<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + std::fmt::Debug,
+{
+    async fn call(&mut self, request: Request) -> Result<Response, ModbusError> {
+        let frame = encode_frame(self.slave.0, &request.encode());
+        self.port.write_all(&frame).await?;
+
+        loop {
+            let frame = match read_response(&mut self.port).await {
+                Err(ModbusError::UnexpectedResponseError) => {
+                    self.wait_silence().await?;
+                    return Err(ModbusError::UnexpectedResponseError);
+                }
+                res => res?,
+            };
+            let (body, crc) = frame.split_at(frame.len() - 2);
+            if crc16(body).to_le_bytes() != crc {
+                warn!("Received a RTU frame with an invalid CRC, dropping it");
+                self.wait_silence().await?;
+                return Err(ModbusError::UnexpectedResponseError);
+            }
+            if body[0] != self.slave.0 {
+                debug!("Received a frame of slave {0}, dropping it", body[0]);
+                continue;
+            }
+            return Response::decode(&request, &body[1..]);
+        }
+    }
+
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave = slave;
+    }
+
+    async fn discard(&mut self) -> Result<(), ModbusError> {
+        Ok(self.wait_silence().await?)
+    }
+}
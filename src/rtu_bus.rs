@@ -0,0 +1,154 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use tokio::{sync::Mutex, time::Instant};
+use tokio_modbus::Slave;
+
+use crate::{
+    errors::ModbusError,
+    pdu::{Request, Response},
+    register::Register,
+    transaction,
+    transport::Transport,
+    types::{ModBusContext, RTUContext, Timeouts},
+    ModbusDeviceAsync,
+};
+
+#[derive(Debug)]
+struct BusLine {
+    config: RTUContext,
+    ctx: Option<Box<dyn Transport>>,
+    last_frame: Option<Instant>,
+}
+
+// serial line shared by several slaves, the transactions of the devices are serialized on the line
+#[derive(Debug, Clone)]
+pub struct RTUBus {
+    line: Arc<Mutex<BusLine>>,
+    timeouts: Timeouts,
+}
+
+impl RTUBus {
+    // the slave of the context is ignored, each device of the bus has its own
+    pub fn new(config: RTUContext) -> Self {
+        RTUBus::with_line(config, None)
+    }
+
+    // bus on a line that is already opened, the port of the context is opened if the line is lost
+    pub fn from_transport(config: RTUContext, transport: Box<dyn Transport>) -> Self {
+        RTUBus::with_line(config, Some(transport))
+    }
+
+    fn with_line(config: RTUContext, ctx: Option<Box<dyn Transport>>) -> Self {
+        RTUBus {
+            timeouts: config.timeouts,
+            line: Arc::new(Mutex::new(BusLine {
+                config,
+                ctx,
+                last_frame: None,
+            })),
+        }
+    }
+
+    // device for one of the slaves of the bus, it shares the serial port with the other devices
+    pub fn device(
+        &self,
+        slave: Slave,
+        input_registers: HashMap<String, Register>,
+        holding_registers: HashMap<String, Register>,
+    ) -> ModbusDeviceAsync {
+        ModbusDeviceAsync::new(
+            RTUBusContext {
+                bus: self.clone(),
+                slave,
+                timeouts: self.timeouts,
+            }
+            .into(),
+            input_registers,
+            holding_registers,
+        )
+    }
+
+    // open the serial port if it is not already opened by another device
+    pub(crate) async fn transport(
+        &self,
+        slave: Slave,
+        timeout: Option<Duration>,
+    ) -> Result<BusTransport, ModbusError> {
+        let mut line = self.line.lock().await;
+        if line.ctx.is_none() {
+            line.ctx = Some(line.config.open_transport(slave)?);
+            debug!("Opened bus {0}", line.config.port);
+        }
+        Ok(BusTransport {
+            bus: self.clone(),
+            slave,
+            timeout,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct RTUBusContext {
+    pub bus: RTUBus,
+    pub slave: Slave,
+    pub timeouts: Timeouts,
+}
+
+impl Into<ModBusContext> for RTUBusContext {
+    fn into(self) -> ModBusContext {
+        ModBusContext::RTUBus(self)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct BusTransport {
+    bus: RTUBus,
+    slave: Slave,
+    timeout: Option<Duration>, // response timeout of the device
+}
+
+#[async_trait]
+impl Transport for BusTransport {
+    async fn call(&mut self, request: Request) -> Result<Response, ModbusError> {
+        let mut line = self.bus.line.lock().await;
+        if let Some(last) = line.last_frame {
+            let gap = line.config.frame_gap();
+            tokio::time::sleep_until(last + gap).await;
+        }
+
+        let ctx = line
+            .ctx
+            .as_mut()
+            .ok_or(ModbusError::DeviceNotConnectedError)?;
+        ctx.set_slave(self.slave);
+        // the timeout starts once the line is acquired, the transactions of the other slaves are not counted
+        let res = transaction(self.timeout, ctx.call(request)).await;
+        // a silent slave doesn't stop the others, its late response is dropped before the next transaction
+        let discarded = match res {
+            Err(ModbusError::Timeout) => ctx.discard().await,
+            _ => Ok(()),
+        };
+        line.last_frame = Some(Instant::now());
+
+        // the port will be opened again on reconnection
+        let lost = match (&res, discarded) {
+            (_, Err(err)) => {
+                warn!("Could not clear bus {0} ({err})", line.config.port);
+                true
+            }
+            (Err(ModbusError::Timeout), Ok(())) => false,
+            (Err(err), Ok(())) => err.is_connection_error(),
+            (Ok(_), Ok(())) => false,
+        };
+        if lost {
+            line.ctx = None;
+        }
+        res
+    }
+
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave = slave;
+    }
+}
@@ -0,0 +1,526 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::{debug, warn};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream, UdpSocket},
+    task::JoinHandle,
+};
+use tokio_modbus::Slave;
+
+use crate::{
+    ascii::{decode_frame, encode_frame, read_frame},
+    errors::ModbusError,
+    identification::DeviceIdentification,
+    pdu::{
+        encode_exception, DeviceIdentificationPart, Request, Response, ILLEGAL_DATA_ADDRESS,
+        ILLEGAL_DATA_VALUE, ILLEGAL_FUNCTION,
+    },
+    register::{Endianness, Register},
+    rtu::{self, crc16},
+    types::{ModBusRegisters, RegisterValue},
+};
+
+// values of the server by address
+#[derive(Debug, Default)]
+struct ServerState {
+    input_registers: HashMap<u16, u16>,
+    holding_registers: HashMap<u16, u16>,
+    coils: HashMap<u16, bool>,
+    discrete_inputs: HashMap<u16, bool>,
+    identification: BTreeMap<u8, Vec<u8>>,
+}
+
+// addresses that are not defined are rejected like a device would
+fn read_table<T: Copy>(table: &HashMap<u16, T>, addr: u16, nb: u16) -> Result<Vec<T>, u8> {
+    (0..nb)
+        .map(|i| {
+            addr.checked_add(i)
+                .and_then(|addr| table.get(&addr).copied())
+                .ok_or(ILLEGAL_DATA_ADDRESS)
+        })
+        .collect()
+}
+
+// conformity level of the server : basic, regular and extended identification with individual access
+const CONFORMITY_LEVEL: u8 = 0x83;
+// room left for the objects in a response PDU
+const MAX_OBJECTS_LEN: usize = 253 - 7;
+
+// objects of a stream access starting at the requested object, split when they don't fit in a PDU
+fn read_identification(
+    objects: &BTreeMap<u8, Vec<u8>>,
+    code: u8,
+    object_id: u8,
+) -> Result<DeviceIdentificationPart, u8> {
+    let last = match code {
+        0x01 => 0x02,
+        0x02 => 0x7F,
+        0x03 => 0xFF,
+        0x04 => object_id,
+        _ => return Err(ILLEGAL_DATA_VALUE),
+    };
+    // a stream access starting at an unknown object or outside of its category restarts at the first one
+    let object_id = match (code, objects.contains_key(&object_id)) {
+        (_, true) if object_id <= last => object_id,
+        (0x04, false) => return Err(ILLEGAL_DATA_ADDRESS),
+        (_, false) => 0x00,
+    };
+    let mut part = DeviceIdentificationPart {
+        code,
+        conformity_level: CONFORMITY_LEVEL,
+        more_follows: false,
+        next_object_id: 0,
+        objects: Vec::new(),
+    };
+    let mut len = 0;
+    for (id, value) in objects.range(object_id..=last) {
+        let value = &value[..value.len().min(MAX_OBJECTS_LEN - 2)];
+        if len + 2 + value.len() > MAX_OBJECTS_LEN {
+            part.more_follows = true;
+            part.next_object_id = *id;
+            break;
+        }
+        len += 2 + value.len();
+        part.objects.push((*id, value.to_vec()));
+    }
+    Ok(part)
+}
+
+fn write_table<T: Copy>(table: &mut HashMap<u16, T>, addr: u16, values: &[T]) -> Result<(), u8> {
+    let addresses: Vec<u16> = (0..values.len())
+        .map(|i| {
+            u16::try_from(i)
+                .ok()
+                .and_then(|i| addr.checked_add(i))
+                .filter(|addr| table.contains_key(addr))
+                .ok_or(ILLEGAL_DATA_ADDRESS)
+        })
+        .collect::<Result<_, _>>()?;
+    table.extend(addresses.into_iter().zip(values.iter().copied()));
+    Ok(())
+}
+
+impl ServerState {
+    fn process(&mut self, request: Request) -> Result<Response, u8> {
+        Ok(match request {
+            Request::ReadCoils(addr, nb) => Response::ReadCoils(read_table(&self.coils, addr, nb)?),
+            Request::ReadDiscreteInputs(addr, nb) => {
+                Response::ReadDiscreteInputs(read_table(&self.discrete_inputs, addr, nb)?)
+            }
+            Request::ReadInputRegisters(addr, nb) => {
+                Response::ReadInputRegisters(read_table(&self.input_registers, addr, nb)?)
+            }
+            Request::ReadHoldingRegisters(addr, nb) => {
+                Response::ReadHoldingRegisters(read_table(&self.holding_registers, addr, nb)?)
+            }
+            Request::WriteSingleCoil(addr, coil) => {
+                write_table(&mut self.coils, addr, &[coil])?;
+                Response::WriteSingleCoil(addr, coil)
+            }
+            Request::WriteMultipleCoils(addr, coils) => {
+                write_table(&mut self.coils, addr, &coils)?;
+                Response::WriteMultipleCoils(addr, coils.len() as u16)
+            }
+            Request::WriteSingleRegister(addr, value) => {
+                write_table(&mut self.holding_registers, addr, &[value])?;
+                Response::WriteSingleRegister(addr, value)
+            }
+            Request::WriteMultipleRegisters(addr, data) => {
+                write_table(&mut self.holding_registers, addr, &data)?;
+                Response::WriteMultipleRegisters(addr, data.len() as u16)
+            }
+            Request::MaskWriteRegister(addr, and_mask, or_mask) => {
+                let current = read_table(&self.holding_registers, addr, 1)?[0];
+                let value = (current & and_mask) | (or_mask & !and_mask);
+                write_table(&mut self.holding_registers, addr, &[value])?;
+                Response::MaskWriteRegister(addr, and_mask, or_mask)
+            }
+            Request::ReadWriteMultipleRegisters(read_addr, nb, write_addr, data) => {
+                write_table(&mut self.holding_registers, write_addr, &data)?;
+                Response::ReadWriteMultipleRegisters(read_table(
+                    &self.holding_registers,
+                    read_addr,
+                    nb,
+                )?)
+            }
+            Request::ReadDeviceIdentification(code, object_id) => {
+                Response::ReadDeviceIdentification(read_identification(
+                    &self.identification,
+                    code,
+                    object_id,
+                )?)
+            }
+        })
+    }
+}
+
+// answer a request protocol data unit
+fn process_pdu(state: &Mutex<ServerState>, pdu: &[u8]) -> Vec<u8> {
+    let function = pdu.first().copied().unwrap_or_default();
+    let res = Request::decode(pdu).and_then(|request| {
+        debug!("Server received {request:?}");
+        state.lock().unwrap().process(request)
+    });
+    match res {
+        Ok(response) => response.encode(),
+        Err(code) => encode_exception(function, code),
+    }
+}
+
+// frame answering the request with the given MBAP header
+fn mbap_frame(header: &[u8; 7], pdu: &[u8]) -> Vec<u8> {
+    let mut frame = header[0..4].to_vec();
+    frame.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+    frame.push(header[6]);
+    frame.extend_from_slice(pdu);
+    frame
+}
+
+async fn serve_tcp_connection(
+    mut stream: TcpStream,
+    state: Arc<Mutex<ServerState>>,
+) -> std::io::Result<()> {
+    loop {
+        // transaction id, protocol id, length and unit id
+        let mut header = [0u8; 7];
+        stream.read_exact(&mut header).await?;
+        let len: usize = u16::from_be_bytes([header[4], header[5]]).into();
+        let mut pdu = vec![0u8; len.saturating_sub(1)];
+        stream.read_exact(&mut pdu).await?;
+
+        let response = process_pdu(&state, &pdu);
+        stream.write_all(&mbap_frame(&header, &response)).await?;
+    }
+}
+
+async fn serve_rtu_transport<T>(
+    mut transport: T,
+    slave: Slave,
+    state: Arc<Mutex<ServerState>>,
+) -> std::io::Result<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    loop {
+        // slave and function code
+        let mut frame = vec![0u8; 2];
+        transport.read_exact(&mut frame).await?;
+
+        // the length of the frame depends on the function code
+        let remaining = match frame[1] {
+            0x01..=0x06 => 4,
+            0x16 => 6,
+            0x2B => 3,
+            0x0F | 0x10 => {
+                let mut head = [0u8; 5];
+                transport.read_exact(&mut head).await?;
+                frame.extend_from_slice(&head);
+                head[4].into()
+            }
+            0x17 => {
+                let mut head = [0u8; 9];
+                transport.read_exact(&mut head).await?;
+                frame.extend_from_slice(&head);
+                head[8].into()
+            }
+            function => {
+                // the frame can't be delimited, drop everything until the line is silent
+                let mut buf = [0u8; 256];
+                while let Ok(Ok(n)) =
+                    tokio::time::timeout(Duration::from_millis(5), transport.read(&mut buf)).await
+                {
+                    if n == 0 {
+                        break;
+                    }
+                }
+                if frame[0] == slave.0 {
+                    let response = encode_exception(function, ILLEGAL_FUNCTION);
+                    transport
+                        .write_all(&rtu::encode_frame(slave.0, &response))
+                        .await?;
+                }
+                continue;
+            }
+        };
+        let start = frame.len();
+        frame.resize(start + remaining + 2, 0);
+        transport.read_exact(&mut frame[start..]).await?;
+
+        let (body, crc) = frame.split_at(frame.len() - 2);
+        if crc16(body).to_le_bytes() != crc {
+            warn!("Server received a frame with an invalid CRC, dropping it");
+            continue;
+        }
+        // broadcast requests are processed without response
+        if body[0] != slave.0 && body[0] != 0 {
+            continue;
+        }
+        let response = process_pdu(&state, &body[1..]);
+        if body[0] != 0 {
+            transport
+                .write_all(&rtu::encode_frame(slave.0, &response))
+                .await?;
+        }
+    }
+}
+
+async fn serve_ascii_transport<T>(
+    mut transport: T,
+    slave: Slave,
+    state: Arc<Mutex<ServerState>>,
+) -> std::io::Result<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    loop {
+        let frame = read_frame(&mut transport, None).await?;
+        let Some((address, pdu)) = decode_frame(&frame) else {
+            warn!("Server received an invalid ASCII frame, dropping it");
+            continue;
+        };
+        // broadcast requests are processed without response
+        if address != slave.0 && address != 0 {
+            continue;
+        }
+        let response = process_pdu(&state, &pdu);
+        if address != 0 {
+            transport
+                .write_all(&encode_frame(slave.0, &response))
+                .await?;
+        }
+    }
+}
+
+// server simulating a device from its register definitions, the values can be set and inspected by name
+#[derive(Debug, Clone)]
+pub struct ModbusServer {
+    state: Arc<Mutex<ServerState>>,
+    input_registers: HashMap<String, Register>,
+    holding_registers: HashMap<String, Register>,
+    coils: HashMap<String, Register>,
+    discrete_inputs: HashMap<String, Register>,
+    endianness: Endianness,
+}
+
+impl ModbusServer {
+    // every register starts at 0
+    pub fn new(
+        input_registers: HashMap<String, Register>,
+        holding_registers: HashMap<String, Register>,
+    ) -> Self {
+        let server = ModbusServer {
+            state: Arc::new(Mutex::new(ServerState::default())),
+            input_registers: HashMap::new(),
+            holding_registers: HashMap::new(),
+            coils: HashMap::new(),
+            discrete_inputs: HashMap::new(),
+            endianness: Endianness::default(),
+        };
+        server
+            .with_table(ModBusRegisters::INPUT, input_registers)
+            .with_table(ModBusRegisters::HOLDING, holding_registers)
+    }
+
+    // byte ordering used for the registers that don't define their own
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    // objects answered to read device identification requests
+    pub fn with_identification(self, identification: &DeviceIdentification) -> Self {
+        self.state.lock().unwrap().identification = identification.objects();
+        self
+    }
+
+    pub fn with_coils(self, coils: HashMap<String, Register>) -> Self {
+        self.with_table(ModBusRegisters::COIL, coils)
+    }
+
+    pub fn with_discrete_inputs(self, discrete_inputs: HashMap<String, Register>) -> Self {
+        self.with_table(ModBusRegisters::DISCRETE, discrete_inputs)
+    }
+
+    fn with_table(mut self, source: ModBusRegisters, registers: HashMap<String, Register>) -> Self {
+        {
+            let mut state = self.state.lock().unwrap();
+            for reg in registers.values() {
+                let addresses = (0..reg.len).filter_map(|i| reg.addr.checked_add(i));
+                match source {
+                    ModBusRegisters::INPUT => {
+                        state.input_registers.extend(addresses.map(|a| (a, 0)))
+                    }
+                    ModBusRegisters::HOLDING => {
+                        state.holding_registers.extend(addresses.map(|a| (a, 0)))
+                    }
+                    ModBusRegisters::COIL => state.coils.extend(addresses.map(|a| (a, false))),
+                    ModBusRegisters::DISCRETE => {
+                        state.discrete_inputs.extend(addresses.map(|a| (a, false)))
+                    }
+                }
+            }
+        }
+        match source {
+            ModBusRegisters::INPUT => self.input_registers.extend(registers),
+            ModBusRegisters::HOLDING => self.holding_registers.extend(registers),
+            ModBusRegisters::COIL => self.coils.extend(registers),
+            ModBusRegisters::DISCRETE => self.discrete_inputs.extend(registers),
+        }
+        self
+    }
+
+    fn get_register(&self, name: &str) -> Result<(&Register, ModBusRegisters), ModbusError> {
+        [
+            (self.input_registers.get(name), ModBusRegisters::INPUT),
+            (self.holding_registers.get(name), ModBusRegisters::HOLDING),
+            (self.discrete_inputs.get(name), ModBusRegisters::DISCRETE),
+            (self.coils.get(name), ModBusRegisters::COIL),
+        ]
+        .into_iter()
+        .find_map(|(reg, table)| reg.map(|reg| (reg, table)))
+        .ok_or(ModbusError::RegisterDoesNotExistError {
+            name: name.to_string(),
+        })
+    }
+
+    pub fn set_value(&self, name: &str, val: &RegisterValue) -> Result<(), ModbusError> {
+        let (reg, source) = self.get_register(name)?;
+        let mut state = self.state.lock().unwrap();
+
+        match source {
+            ModBusRegisters::COIL | ModBusRegisters::DISCRETE => {
+                let RegisterValue::Boolean(bit) = val else {
+                    return Err(ModbusError::ConversionError);
+                };
+                let table = match source {
+                    ModBusRegisters::COIL => &mut state.coils,
+                    _ => &mut state.discrete_inputs,
+                };
+                table.insert(reg.addr, *bit);
+            }
+            ModBusRegisters::INPUT | ModBusRegisters::HOLDING => {
+                let mut words = reg.encode(val, self.endianness)?;
+                let table = match source {
+                    ModBusRegisters::INPUT => &mut state.input_registers,
+                    _ => &mut state.holding_registers,
+                };
+                // the other bits of the word are kept
+                if let Some(mask) = reg.mask {
+                    let current = table.get(&reg.addr).copied().unwrap_or_default();
+                    words = vec![(current & !mask) | words[0]];
+                }
+                let addresses = (0..words.len() as u16).filter_map(|i| reg.addr.checked_add(i));
+                table.extend(addresses.zip(words));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_value(&self, name: &str) -> Result<RegisterValue, ModbusError> {
+        let (reg, source) = self.get_register(name)?;
+        let state = self.state.lock().unwrap();
+
+        let value = match source {
+            ModBusRegisters::COIL => read_table(&state.coils, reg.addr, 1).map(|bits| bits[0]),
+            ModBusRegisters::DISCRETE => {
+                read_table(&state.discrete_inputs, reg.addr, 1).map(|bits| bits[0])
+            }
+            ModBusRegisters::INPUT | ModBusRegisters::HOLDING => {
+                let table = match source {
+                    ModBusRegisters::INPUT => &state.input_registers,
+                    _ => &state.holding_registers,
+                };
+                let words = read_table(table, reg.addr, reg.len)
+                    .map_err(|_| ModbusError::ConversionError)?;
+                return Ok(reg.decode(words, self.endianness)?);
+            }
+        };
+        value
+            .map(RegisterValue::Boolean)
+            .map_err(|_| ModbusError::ConversionError)
+    }
+
+    // listen for Modbus TCP connections, binding port 0 gives an ephemeral port
+    pub async fn serve_tcp(&self, addr: SocketAddr) -> Result<SocketAddr, ModbusError> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        debug!("Server listening on {local_addr}");
+
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let state = state.clone();
+                        tokio::spawn(async move {
+                            if let Err(err) = serve_tcp_connection(stream, state).await {
+                                debug!("Connection with {peer} closed ({err})");
+                            }
+                        });
+                    }
+                    Err(err) => warn!("Server could not accept a connection ({err})"),
+                }
+            }
+        });
+        Ok(local_addr)
+    }
+
+    // answer Modbus UDP datagrams, binding port 0 gives an ephemeral port
+    pub async fn serve_udp(&self, addr: SocketAddr) -> Result<SocketAddr, ModbusError> {
+        let socket = UdpSocket::bind(addr).await?;
+        let local_addr = socket.local_addr()?;
+        debug!("Server listening on {local_addr} (UDP)");
+
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 260];
+            loop {
+                let (len, peer) = match socket.recv_from(&mut buf).await {
+                    Ok(res) => res,
+                    Err(err) => {
+                        warn!("Server could not receive a datagram ({err})");
+                        continue;
+                    }
+                };
+                let Ok(header) = <[u8; 7]>::try_from(&buf[..len.min(7)]) else {
+                    continue;
+                };
+                let response = process_pdu(&state, &buf[7..len]);
+                if let Err(err) = socket.send_to(&mbap_frame(&header, &response), peer).await {
+                    warn!("Server could not answer {peer} ({err})");
+                }
+            }
+        });
+        Ok(local_addr)
+    }
+
+    // answer the requests sent to this slave on a serial line (ex : a pseudo terminal)
+    pub fn serve_rtu<T>(&self, transport: T, slave: Slave) -> JoinHandle<Result<(), ModbusError>>
+    where
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            serve_rtu_transport(transport, slave, state).await?;
+            Ok(())
+        })
+    }
+
+    // answer the requests sent to this slave on a Modbus ASCII serial line
+    pub fn serve_ascii<T>(&self, transport: T, slave: Slave) -> JoinHandle<Result<(), ModbusError>>
+    where
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            serve_ascii_transport(transport, slave, state).await?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,98 @@
+use std::{borrow::Cow, fmt::Debug};
+
+use async_trait::async_trait;
+use tokio_modbus::{
+    client::{Client, Context},
+    prelude::{Reader, SlaveContext, Writer},
+    Exception, Quantity, Slave,
+};
+
+use crate::{
+    errors::ModbusError,
+    pdu::{DeviceIdentificationPart, Request, Response, MEI_READ_DEVICE_ID},
+};
+
+// link used by a device to run its transactions
+#[async_trait]
+pub trait Transport: Send + Debug {
+    async fn call(&mut self, request: Request) -> Result<Response, ModbusError>;
+    fn set_slave(&mut self, slave: Slave);
+    // drop what is left of a transaction without response, the link stays open
+    async fn discard(&mut self) -> Result<(), ModbusError> {
+        Ok(())
+    }
+}
+
+fn flatten<T>(res: Result<Result<T, Exception>, tokio_modbus::Error>) -> Result<T, ModbusError> {
+    match res {
+        Ok(res) => match res {
+            Ok(res) => Ok(res),
+            Err(err) => Err(err.into()),
+        },
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[async_trait]
+impl Transport for Context {
+    async fn call(&mut self, request: Request) -> Result<Response, ModbusError> {
+        Ok(match request {
+            Request::ReadCoils(addr, nb) => {
+                Response::ReadCoils(flatten(self.read_coils(addr, nb).await)?)
+            }
+            Request::ReadDiscreteInputs(addr, nb) => {
+                Response::ReadDiscreteInputs(flatten(self.read_discrete_inputs(addr, nb).await)?)
+            }
+            Request::ReadInputRegisters(addr, nb) => {
+                Response::ReadInputRegisters(flatten(self.read_input_registers(addr, nb).await)?)
+            }
+            Request::ReadHoldingRegisters(addr, nb) => Response::ReadHoldingRegisters(flatten(
+                self.read_holding_registers(addr, nb).await,
+            )?),
+            Request::WriteSingleCoil(addr, coil) => {
+                flatten(self.write_single_coil(addr, coil).await)?;
+                Response::WriteSingleCoil(addr, coil)
+            }
+            Request::WriteMultipleCoils(addr, coils) => {
+                flatten(self.write_multiple_coils(addr, &coils).await)?;
+                Response::WriteMultipleCoils(addr, coils.len() as Quantity)
+            }
+            Request::WriteSingleRegister(addr, value) => {
+                flatten(self.write_single_register(addr, value).await)?;
+                Response::WriteSingleRegister(addr, value)
+            }
+            Request::WriteMultipleRegisters(addr, data) => {
+                flatten(self.write_multiple_registers(addr, &data).await)?;
+                Response::WriteMultipleRegisters(addr, data.len() as Quantity)
+            }
+            Request::MaskWriteRegister(addr, and_mask, or_mask) => {
+                flatten(self.masked_write_register(addr, and_mask, or_mask).await)?;
+                Response::MaskWriteRegister(addr, and_mask, or_mask)
+            }
+            Request::ReadWriteMultipleRegisters(read_addr, nb, write_addr, data) => {
+                Response::ReadWriteMultipleRegisters(flatten(
+                    self.read_write_multiple_registers(read_addr, nb, write_addr, &data)
+                        .await,
+                )?)
+            }
+            // encapsulated interface transport is not implemented by tokio-modbus
+            Request::ReadDeviceIdentification(code, object_id) => {
+                let data = vec![MEI_READ_DEVICE_ID, code, object_id];
+                let request = tokio_modbus::Request::Custom(0x2B, Cow::Owned(data));
+                match flatten(Client::call(self, request).await)? {
+                    tokio_modbus::Response::Custom(0x2B, data) => {
+                        Response::ReadDeviceIdentification(
+                            DeviceIdentificationPart::decode(&data)
+                                .ok_or(ModbusError::UnexpectedResponseError)?,
+                        )
+                    }
+                    _ => return Err(ModbusError::UnexpectedResponseError),
+                }
+            }
+        })
+    }
+
+    fn set_slave(&mut self, slave: Slave) {
+        SlaveContext::set_slave(self, slave);
+    }
+}
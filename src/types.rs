@@ -1,30 +1,221 @@
-use std::{array::TryFromSliceError, net::SocketAddr};
+use std::{array::TryFromSliceError, net::SocketAddr, time::Duration};
 
-use crate::register;
+use crate::ascii::AsciiTransport;
+use crate::errors::{ModbusError, RegisterError};
+use crate::register::{self, Endianness, StringFormat};
+use crate::rtu::{RtuTransport, MIN_FRAME_GAP};
+use crate::rtu_bus::RTUBusContext;
+use crate::transport::Transport;
 use serde::{Deserialize, Serialize};
 use tokio_modbus::Slave;
+use tokio_serial::{DataBits, FlowControl, Parity, SerialStream, StopBits};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ModBusRegisters {
     INPUT,
     HOLDING,
+    COIL,
+    DISCRETE,
+}
+
+// no timeout is applied when they are not set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timeouts {
+    pub connect: Option<Duration>,       // not applicable to serial ports
+    pub response: Option<Duration>,      // for each request sent to the device
+    pub inter_request: Option<Duration>, // minimum delay between the end of a transaction and the next request
+}
+
+// how the frames are delimited on a serial line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SerialFraming {
+    #[default]
+    Rtu, // binary frames with a CRC, delimited by silences
+    Ascii, // hexadecimal frames with a LRC, from ':' to CRLF
+}
+
+// how the registers with a mask are written, the other bits of the word are kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BitWriteMode {
+    #[default]
+    ReadModifyWrite, // the word is read then written back, not atomic
+    MaskWrite, // mask write register (FC22), not supported by all the devices
 }
 
 #[derive(Debug)]
 pub struct TCPContext {
     pub addr: SocketAddr,
+    pub slave: Slave, // unit identifier, used to reach the devices behind a gateway
+    pub timeouts: Timeouts,
+}
+// Modbus UDP, datagrams can be lost so the requests are sent again when there is no response
+#[derive(Debug)]
+pub struct UDPContext {
+    pub addr: SocketAddr,
+    pub slave: Slave,
+    pub timeouts: Timeouts,
+    pub retry_timeout: Duration, // time to wait for a response before sending a read again
+    pub retries: u32,
+}
+// serial device reached through a transparent serial to Ethernet converter, the RTU frames are sent as is
+#[derive(Debug)]
+pub struct RTUOverTCPContext {
+    pub addr: SocketAddr,
+    pub slave: Slave,
+    pub timeouts: Timeouts,
 }
 #[derive(Debug)]
 pub struct RTUContext {
     pub port: String,
     pub slave: Slave,
     pub speed: u32,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub data_bits: DataBits,
+    pub flow_control: FlowControl,
+    pub timeouts: Timeouts,
+    pub framing: SerialFraming,
+    pub inter_char_timeout: Option<Duration>, // ASCII framing only, the frames interrupted for longer are dropped
+}
+
+impl TCPContext {
+    pub fn new(addr: SocketAddr) -> Self {
+        TCPContext {
+            addr,
+            slave: Slave::tcp_device(),
+            timeouts: Timeouts::default(),
+        }
+    }
+}
+impl UDPContext {
+    pub fn new(addr: SocketAddr) -> Self {
+        UDPContext {
+            addr,
+            slave: Slave::tcp_device(),
+            timeouts: Timeouts::default(),
+            retry_timeout: Duration::from_millis(500),
+            retries: 2,
+        }
+    }
+}
+impl RTUOverTCPContext {
+    pub fn new(addr: SocketAddr, slave: Slave) -> Self {
+        RTUOverTCPContext {
+            addr,
+            slave,
+            timeouts: Timeouts::default(),
+        }
+    }
+}
+impl RTUContext {
+    // the serial line defaults to 8E1 as required by the modbus specification
+    pub fn new(port: String, slave: Slave, speed: u32) -> Self {
+        RTUContext {
+            port,
+            slave,
+            speed,
+            parity: Parity::Even,
+            stop_bits: StopBits::One,
+            data_bits: DataBits::Eight,
+            flow_control: FlowControl::None,
+            timeouts: Timeouts::default(),
+            framing: SerialFraming::Rtu,
+            inter_char_timeout: None,
+        }
+    }
+
+    // Modbus ASCII line, 7E1 as required by the modbus specification
+    pub fn new_ascii(port: String, slave: Slave, speed: u32) -> Self {
+        RTUContext {
+            data_bits: DataBits::Seven,
+            framing: SerialFraming::Ascii,
+            inter_char_timeout: Some(Duration::from_secs(1)),
+            ..RTUContext::new(port, slave, speed)
+        }
+    }
+
+    // silence required between two frames (3.5 characters), fixed above 19200 bauds by the specification
+    pub fn frame_gap(&self) -> Duration {
+        if self.speed > 19200 {
+            return MIN_FRAME_GAP;
+        }
+        let data_bits = match self.data_bits {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        let parity_bits = match self.parity {
+            Parity::None => 0,
+            Parity::Odd | Parity::Even => 1,
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        let char_bits = 1 + data_bits + parity_bits + stop_bits;
+
+        Duration::from_secs_f64(3.5 * char_bits as f64 / self.speed.max(1) as f64)
+    }
+
+    pub(crate) fn open_port(&self) -> Result<SerialStream, tokio_serial::Error> {
+        let builder = tokio_serial::new(self.port.clone(), self.speed)
+            .stop_bits(self.stop_bits)
+            .parity(self.parity)
+            .data_bits(self.data_bits)
+            .flow_control(self.flow_control);
+        SerialStream::open(&builder)
+    }
+
+    pub(crate) fn open_transport(&self, slave: Slave) -> Result<Box<dyn Transport>, ModbusError> {
+        let port = self.open_port()?;
+        let transport: Box<dyn Transport> = match self.framing {
+            SerialFraming::Rtu => Box::new(RtuTransport::new(port, slave, self.frame_gap())),
+            SerialFraming::Ascii => {
+                Box::new(AsciiTransport::new(port, slave, self.inter_char_timeout))
+            }
+        };
+        Ok(transport)
+    }
+}
+
+impl ModBusContext {
+    pub fn slave(&self) -> Slave {
+        match self {
+            ModBusContext::TCP(ctx) => ctx.slave,
+            ModBusContext::RTU(ctx) => ctx.slave,
+            ModBusContext::RTUBus(ctx) => ctx.slave,
+            ModBusContext::RTUOverTCP(ctx) => ctx.slave,
+            ModBusContext::UDP(ctx) => ctx.slave,
+        }
+    }
+    pub fn set_slave(&mut self, slave: Slave) {
+        match self {
+            ModBusContext::TCP(ctx) => ctx.slave = slave,
+            ModBusContext::RTU(ctx) => ctx.slave = slave,
+            ModBusContext::RTUBus(ctx) => ctx.slave = slave,
+            ModBusContext::RTUOverTCP(ctx) => ctx.slave = slave,
+            ModBusContext::UDP(ctx) => ctx.slave = slave,
+        }
+    }
+    pub fn timeouts(&self) -> Timeouts {
+        match self {
+            ModBusContext::TCP(ctx) => ctx.timeouts,
+            ModBusContext::RTU(ctx) => ctx.timeouts,
+            ModBusContext::RTUBus(ctx) => ctx.timeouts,
+            ModBusContext::RTUOverTCP(ctx) => ctx.timeouts,
+            ModBusContext::UDP(ctx) => ctx.timeouts,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum ModBusContext {
     TCP(TCPContext),
     RTU(RTUContext),
+    RTUBus(RTUBusContext),
+    RTUOverTCP(RTUOverTCPContext),
+    UDP(UDPContext),
 }
 
 impl Into<ModBusContext> for TCPContext {
@@ -37,20 +228,38 @@ impl Into<ModBusContext> for RTUContext {
         ModBusContext::RTU(self)
     }
 }
+impl Into<ModBusContext> for UDPContext {
+    fn into(self) -> ModBusContext {
+        ModBusContext::UDP(self)
+    }
+}
+impl Into<ModBusContext> for RTUOverTCPContext {
+    fn into(self) -> ModBusContext {
+        ModBusContext::RTUOverTCP(self)
+    }
+}
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum RegisterValue {
     U16(u16),
     U32(u32),
     U64(u64),
     U128(u128),
+    S16(i16),
     S32(i32),
+    S64(i64),
     Enum16(u16),
+    Label(u16, String), // value of an enum with its label
     Sized([u8; 66]),
     Float32(f32),
+    Float64(f64),
     Boolean(bool),
+    String(String),
 }
 
+// outcome of the read of a single register
+pub type RegisterResult = Result<RegisterValue, RegisterError>;
+
 #[derive(Serialize, Deserialize)]
 pub enum DataType {
     #[serde(alias = "Uint16")]
@@ -59,14 +268,20 @@ pub enum DataType {
     UInt32,
     UInt64,
     UInt128,
+    Int16,
     Int32,
+    Int64,
     Enum16,
     #[serde(rename = "Sized+Uint16[31]")]
     Sized,
     #[serde(rename = "IEEE-754 float32")]
     Float32,
+    #[serde(rename = "IEEE-754 float64")]
+    Float64,
     #[serde(rename = "boolean")]
     Boolean,
+    #[serde(alias = "string")]
+    String,
 }
 
 impl Into<register::DataType> for DataType {
@@ -76,11 +291,16 @@ impl Into<register::DataType> for DataType {
             Self::UInt32 => register::DataType::UInt32,
             Self::UInt64 => register::DataType::UInt64,
             Self::UInt128 => register::DataType::UInt128,
+            Self::Int16 => register::DataType::Int16,
             Self::Int32 => register::DataType::Int32,
+            Self::Int64 => register::DataType::Int64,
             Self::Enum16 => register::DataType::Enum16,
-            Self::Sized => register::DataType::Sized,
+            // legacy name of the strings, their length is the one of the register
+            Self::Sized => register::DataType::String(StringFormat::default()),
             Self::Float32 => register::DataType::Float32,
+            Self::Float64 => register::DataType::Float64,
             Self::Boolean => register::DataType::Boolean,
+            Self::String => register::DataType::String(StringFormat::default()),
         }
     }
 }
@@ -91,76 +311,205 @@ impl From<f32> for RegisterValue {
     }
 }
 
+impl From<f64> for RegisterValue {
+    fn from(value: f64) -> Self {
+        RegisterValue::Float64(value)
+    }
+}
+
 impl TryFrom<(Vec<u16>, register::DataType)> for RegisterValue {
     fn try_from((raw, kind): (Vec<u16>, register::DataType)) -> Result<Self, Self::Error> {
-        let raw_b: Vec<u8> = raw
-            .iter()
-            .map(|v| v.to_be_bytes())
-            .flatten()
-            .rev()
-            .collect();
+        (raw, kind, Endianness::default()).try_into()
+    }
+
+    type Error = Vec<u8>;
+}
+
+impl TryFrom<(Vec<u16>, register::DataType, Endianness)> for RegisterValue {
+    fn try_from(
+        (raw, kind, endianness): (Vec<u16>, register::DataType, Endianness),
+    ) -> Result<Self, Self::Error> {
+        let raw_b: Vec<u8> = raw.iter().flat_map(|v| v.to_be_bytes()).collect();
+        // big endian bytes of the value
+        let value_b = endianness.reorder(&raw_b);
         match kind {
-            register::DataType::UInt16 => Ok(RegisterValue::U16(raw[0])),
-            register::DataType::UInt32 => match raw_b.try_into() {
-                Ok(res) => Ok(RegisterValue::U32(u32::from_le_bytes(res))),
+            register::DataType::UInt16 => match value_b.try_into() {
+                Ok(res) => Ok(RegisterValue::U16(u16::from_be_bytes(res))),
+                Err(err) => Err(err),
+            },
+            register::DataType::UInt32 => match value_b.try_into() {
+                Ok(res) => Ok(RegisterValue::U32(u32::from_be_bytes(res))),
+                Err(err) => Err(err),
+            },
+            register::DataType::UInt64 => match value_b.try_into() {
+                Ok(res) => Ok(RegisterValue::U64(u64::from_be_bytes(res))),
                 Err(err) => Err(err),
             },
-            register::DataType::UInt64 => match raw_b.try_into() {
-                Ok(res) => Ok(RegisterValue::U64(u64::from_le_bytes(res))),
+            register::DataType::UInt128 => match value_b.try_into() {
+                Ok(res) => Ok(RegisterValue::U128(u128::from_be_bytes(res))),
                 Err(err) => Err(err),
             },
-            register::DataType::UInt128 => match raw_b.try_into() {
-                Ok(res) => Ok(RegisterValue::U128(u128::from_le_bytes(res))),
+            register::DataType::Int16 => match value_b.try_into() {
+                Ok(res) => Ok(RegisterValue::S16(i16::from_be_bytes(res))),
                 Err(err) => Err(err),
             },
-            register::DataType::Int32 => match raw_b.try_into() {
-                Ok(res) => Ok(RegisterValue::S32(i32::from_le_bytes(res))),
+            register::DataType::Int32 => match value_b.try_into() {
+                Ok(res) => Ok(RegisterValue::S32(i32::from_be_bytes(res))),
                 Err(err) => Err(err),
             },
-            register::DataType::Enum16 => Ok(RegisterValue::Enum16(raw[0])),
-            register::DataType::Sized => match raw_b.try_into() {
-                Ok(res) => Ok(RegisterValue::Sized(res)),
+            register::DataType::Int64 => match value_b.try_into() {
+                Ok(res) => Ok(RegisterValue::S64(i64::from_be_bytes(res))),
                 Err(err) => Err(err),
             },
-            register::DataType::Float32 => match raw_b.try_into() {
-                Ok(res) => Ok(RegisterValue::Float32(f32::from_le_bytes(res))),
+            register::DataType::Enum16 => match value_b.try_into() {
+                Ok(res) => Ok(RegisterValue::Enum16(u16::from_be_bytes(res))),
                 Err(err) => Err(err),
             },
-            register::DataType::Boolean => Ok(RegisterValue::Boolean(!raw[0] == 0)),
+            // raw byte arrays are not affected by the endianness
+            register::DataType::Sized => {
+                match raw_b.into_iter().rev().collect::<Vec<u8>>().try_into() {
+                    Ok(res) => Ok(RegisterValue::Sized(res)),
+                    Err(err) => Err(err),
+                }
+            }
+            register::DataType::Float32 => match value_b.try_into() {
+                Ok(res) => Ok(RegisterValue::Float32(f32::from_be_bytes(res))),
+                Err(err) => Err(err),
+            },
+            register::DataType::Float64 => match value_b.try_into() {
+                Ok(res) => Ok(RegisterValue::Float64(f64::from_be_bytes(res))),
+                Err(err) => Err(err),
+            },
+            register::DataType::Boolean => match raw[..] {
+                [word] => Ok(RegisterValue::Boolean(word != 0)),
+                _ => Err(raw_b),
+            },
+            // strings are not affected by the endianness
+            register::DataType::String(format) => match format.decode(&raw) {
+                Some(res) => Ok(RegisterValue::String(res)),
+                None => Err(raw_b),
+            },
         }
     }
 
     type Error = Vec<u8>;
 }
 
-impl TryInto<Vec<u16>> for RegisterValue {
-    type Error = TryFromSliceError;
+impl RegisterValue {
+    // numeric value, used to apply the scaling of the registers
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            RegisterValue::U16(val) => Some((*val).into()),
+            RegisterValue::U32(val) => Some((*val).into()),
+            RegisterValue::U64(val) => Some(*val as f64),
+            RegisterValue::U128(val) => Some(*val as f64),
+            RegisterValue::S16(val) => Some((*val).into()),
+            RegisterValue::S32(val) => Some((*val).into()),
+            RegisterValue::S64(val) => Some(*val as f64),
+            RegisterValue::Float32(val) => Some((*val).into()),
+            RegisterValue::Float64(val) => Some(*val),
+            RegisterValue::Enum16(_)
+            | RegisterValue::Label(..)
+            | RegisterValue::Sized(_)
+            | RegisterValue::Boolean(_)
+            | RegisterValue::String(_) => None,
+        }
+    }
 
-    fn try_into(self) -> Result<Vec<u16>, Self::Error> {
+    // value of the given numeric type, integers are rounded and must be in range
+    pub fn from_f64(val: f64, kind: register::DataType) -> Option<RegisterValue> {
+        let int = val.round();
+        let in_range = |min: f64, max: f64| int.is_finite() && int >= min && int <= max;
+        match kind {
+            register::DataType::UInt16 if in_range(0.0, u16::MAX.into()) => {
+                Some(RegisterValue::U16(int as u16))
+            }
+            register::DataType::UInt32 if in_range(0.0, u32::MAX.into()) => {
+                Some(RegisterValue::U32(int as u32))
+            }
+            register::DataType::UInt64 if in_range(0.0, u64::MAX as f64) => {
+                Some(RegisterValue::U64(int as u64))
+            }
+            register::DataType::UInt128 if in_range(0.0, u128::MAX as f64) => {
+                Some(RegisterValue::U128(int as u128))
+            }
+            register::DataType::Int16 if in_range(i16::MIN.into(), i16::MAX.into()) => {
+                Some(RegisterValue::S16(int as i16))
+            }
+            register::DataType::Int32 if in_range(i32::MIN.into(), i32::MAX.into()) => {
+                Some(RegisterValue::S32(int as i32))
+            }
+            register::DataType::Int64 if in_range(i64::MIN as f64, i64::MAX as f64) => {
+                Some(RegisterValue::S64(int as i64))
+            }
+            register::DataType::Float32 if val.is_finite() => {
+                Some(RegisterValue::Float32(val as f32))
+            }
+            register::DataType::Float64 if val.is_finite() => Some(RegisterValue::Float64(val)),
+            _ => None,
+        }
+    }
+
+    // convert the value to the registers to send using the given byte ordering
+    pub fn to_words(&self, endianness: Endianness) -> Result<Vec<u16>, ModbusError> {
         let bytearray = match self {
-            RegisterValue::U16(val) => val.to_le_bytes().to_vec(),
-            RegisterValue::U32(val) => val.to_le_bytes().to_vec(),
-            RegisterValue::U64(val) => val.to_le_bytes().to_vec(),
-            RegisterValue::U128(val) => val.to_le_bytes().to_vec(),
-            RegisterValue::S32(val) => val.to_le_bytes().to_vec(),
-            RegisterValue::Enum16(val) => val.to_le_bytes().to_vec(),
-            RegisterValue::Sized(val) => val.to_vec(),
-            RegisterValue::Float32(val) => val.to_le_bytes().to_vec(),
+            RegisterValue::U16(val) => val.to_be_bytes().to_vec(),
+            RegisterValue::U32(val) => val.to_be_bytes().to_vec(),
+            RegisterValue::U64(val) => val.to_be_bytes().to_vec(),
+            RegisterValue::U128(val) => val.to_be_bytes().to_vec(),
+            RegisterValue::S16(val) => val.to_be_bytes().to_vec(),
+            RegisterValue::S32(val) => val.to_be_bytes().to_vec(),
+            RegisterValue::S64(val) => val.to_be_bytes().to_vec(),
+            RegisterValue::Enum16(val) => val.to_be_bytes().to_vec(),
+            RegisterValue::Label(val, _) => val.to_be_bytes().to_vec(),
+            // raw byte arrays are not affected by the endianness
+            RegisterValue::Sized(val) => {
+                return val
+                    .chunks(2)
+                    .map(|v| match v.try_into() {
+                        Ok(arr) => Ok(u16::from_le_bytes(arr)),
+                        Err(err) => Err(err),
+                    })
+                    .rev()
+                    .collect::<Result<Vec<u16>, TryFromSliceError>>()
+                    .map_err(ModbusError::from)
+            }
+            RegisterValue::Float32(val) => val.to_be_bytes().to_vec(),
+            RegisterValue::Float64(val) => val.to_be_bytes().to_vec(),
+            // the length of the register is not known, use Register::encode to pad the string,
+            // an empty string has no word to write
+            RegisterValue::String(val) => {
+                let len = u16::try_from(val.len().div_ceil(2))
+                    .map_err(|_| ModbusError::ConversionError)?;
+                return StringFormat::default()
+                    .encode(val, len)
+                    .filter(|words| !words.is_empty())
+                    .ok_or(ModbusError::ConversionError);
+            }
             RegisterValue::Boolean(val) => match val {
                 true => 1 as u16,
                 false => 0,
             }
-            .to_le_bytes()
+            .to_be_bytes()
             .to_vec(),
         };
 
-        bytearray
+        endianness
+            .reorder(&bytearray)
             .chunks(2)
             .map(|v| match v.try_into() {
-                Ok(arr) => Ok(u16::from_le_bytes(arr)),
+                Ok(arr) => Ok(u16::from_be_bytes(arr)),
                 Err(err) => Err(err),
             })
-            .rev()
-            .collect()
+            .collect::<Result<Vec<u16>, TryFromSliceError>>()
+            .map_err(ModbusError::from)
+    }
+}
+
+impl TryInto<Vec<u16>> for RegisterValue {
+    type Error = ModbusError;
+
+    fn try_into(self) -> Result<Vec<u16>, Self::Error> {
+        self.to_words(Endianness::default())
     }
 }
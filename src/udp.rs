@@ -0,0 +1,113 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use tokio::net::UdpSocket;
+use tokio_modbus::Slave;
+
+use crate::{
+    errors::ModbusError,
+    pdu::{Request, Response},
+    transport::Transport,
+};
+
+// largest MBAP header and PDU
+const MAX_DATAGRAM_LEN: usize = 260;
+
+// Modbus UDP, the MBAP frames of Modbus TCP are sent as datagrams
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+    slave: Slave,
+    next_id: u16,
+    retry_timeout: Duration,
+    retries: u32,
+}
+
+impl UdpTransport {
+    // a read is sent again when no response is received after retry_timeout, up to retries times,
+    // a write is sent once and its response is waited for as long, a lost response would apply it twice
+    pub async fn connect(
+        addr: SocketAddr,
+        slave: Slave,
+        retry_timeout: Duration,
+        retries: u32,
+    ) -> Result<Self, ModbusError> {
+        let local = match addr {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let socket = UdpSocket::bind(SocketAddr::new(local, 0)).await?;
+        socket.connect(addr).await?;
+        Ok(UdpTransport {
+            socket,
+            slave,
+            next_id: 0,
+            retry_timeout,
+            retries,
+        })
+    }
+
+    // wait for the response of the transaction, the datagrams of the other transactions are dropped
+    async fn receive(&self, id: u16) -> Result<Vec<u8>, ModbusError> {
+        let mut buf = [0u8; MAX_DATAGRAM_LEN];
+        loop {
+            let len = self.socket.recv(&mut buf).await?;
+            if len < 7 {
+                warn!("Received a datagram without MBAP header, dropping it");
+                continue;
+            }
+            let response_id = u16::from_be_bytes([buf[0], buf[1]]);
+            if response_id != id {
+                debug!("Received a response to the transaction {response_id} instead of {id}, dropping it");
+                continue;
+            }
+            return Ok(buf[7..len].to_vec());
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for UdpTransport {
+    async fn call(&mut self, request: Request) -> Result<Response, ModbusError> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        let pdu = request.encode();
+        let mut frame = Vec::with_capacity(pdu.len() + 7);
+        frame.extend(id.to_be_bytes());
+        frame.extend([0, 0]);
+        frame.extend((pdu.len() as u16 + 1).to_be_bytes());
+        frame.push(self.slave.0);
+        frame.extend(pdu);
+
+        let retry = request.is_read() || matches!(request, Request::ReadDeviceIdentification(..));
+        if !retry {
+            self.socket.send(&frame).await?;
+            let timeout = self
+                .retry_timeout
+                .saturating_mul(self.retries.saturating_add(1));
+            return match tokio::time::timeout(timeout, self.receive(id)).await {
+                Ok(pdu) => Response::decode(&request, &pdu?),
+                Err(_) => Err(ModbusError::Timeout),
+            };
+        }
+        for attempt in 0..=self.retries {
+            if attempt > 0 {
+                debug!("No response to the transaction {id}, sending it again ({attempt})");
+            }
+            self.socket.send(&frame).await?;
+            if let Ok(pdu) = tokio::time::timeout(self.retry_timeout, self.receive(id)).await {
+                return Response::decode(&request, &pdu?);
+            }
+        }
+        Err(ModbusError::Timeout)
+    }
+
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave = slave;
+    }
+}
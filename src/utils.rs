@@ -2,7 +2,10 @@ use std::{collections::HashMap, fs::File};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{register::Register, types::DataType};
+use crate::{
+    register::{self, Endianness, Register, StringEncoding, StringFormat, WriteStrategy},
+    types::DataType,
+};
 
 fn return_true() -> bool {
     true
@@ -17,12 +20,43 @@ struct RawRegister {
     len: u16,
     #[serde(default = "return_true")]
     read: bool,
+    #[serde(default)]
+    endianness: Option<Endianness>,
+    // only used by the strings
+    #[serde(default)]
+    encoding: StringEncoding,
+    #[serde(default)]
+    byte_swap: bool,
+    #[serde(default = "return_true")]
+    trim: bool,
+    // linear scaling of the value read, with its unit
+    #[serde(default)]
+    scale: Option<f64>,
+    #[serde(default)]
+    offset: Option<f64>,
+    #[serde(default)]
+    unit: Option<String>,
+    // value to label table of the enums
+    #[serde(default)]
+    labels: Option<HashMap<u16, String>>,
+    // part of the word holding the value, as a bit index or a mask
+    #[serde(default)]
+    bit: Option<u8>,
+    #[serde(default)]
+    mask: Option<u16>,
+    #[serde(default)]
+    write_strategy: Option<WriteStrategy>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct RegistersFormat {
     metaid: String,
     result: String,
+    // default for all the registers of the file
+    #[serde(default)]
+    endianness: Option<Endianness>,
+    #[serde(default)]
+    write_strategy: Option<WriteStrategy>,
     registers: Vec<RawRegister>,
 }
 
@@ -30,14 +64,47 @@ pub fn get_defs_from_json(input: File) -> Result<HashMap<String, Register>, serd
     let raw: RegistersFormat = serde_json::from_reader(input)?;
     let mut m = HashMap::<String, Register>::new();
     for f in raw.registers {
+        let data_type = match f.type_.into() {
+            register::DataType::String(_) => register::DataType::String(StringFormat {
+                encoding: f.encoding,
+                byte_swap: f.byte_swap,
+                trim: f.trim,
+            }),
+            data_type => data_type,
+        };
         m.insert(
             f.name.clone(),
             Register {
                 name: f.name,
                 addr: f.id,
                 len: f.len / 16,
-                data_type: f.type_.into(),
+                data_type,
+                read: f.read,
+                endianness: f.endianness.or(raw.endianness),
+                scale: f.scale,
+                offset: f.offset,
+                unit: f.unit,
+                labels: f.labels,
+                mask: f
+                    .mask
+                    .or(f.bit.and_then(|bit| 1u16.checked_shl(bit.into()))),
+                write_strategy: f.write_strategy.or(raw.write_strategy),
+            },
+        );
+    }
+    return Ok(m);
+}
+
+// coils and discrete inputs are single bits, the type and length of the definitions are ignored
+pub fn get_bit_defs_from_json(input: File) -> Result<HashMap<String, Register>, serde_json::Error> {
+    let raw: RegistersFormat = serde_json::from_reader(input)?;
+    let mut m = HashMap::<String, Register>::new();
+    for f in raw.registers {
+        m.insert(
+            f.name.clone(),
+            Register {
                 read: f.read,
+                ..Register::new(&f.name, f.id, 1, register::DataType::Boolean)
             },
         );
     }
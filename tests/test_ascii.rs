@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use modbus_device::ascii::{decode_frame, encode_frame, lrc, AsciiTransport};
+use modbus_device::pdu::{Request, Response};
+use modbus_device::register::{DataType, Register};
+use modbus_device::server::ModbusServer;
+use modbus_device::transport::Transport;
+use modbus_device::types::RegisterValue;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_modbus::Slave;
+
+fn registers() -> HashMap<String, Register> {
+    HashMap::from([(
+        "Energy".to_string(),
+        Register::new("Energy", 0x6B, 2, DataType::UInt32),
+    )])
+}
+
+#[test]
+fn test_frames() {
+    // example of the specification : read 3 holding registers from 0x6B on slave 0x11
+    let pdu = [0x03, 0x00, 0x6B, 0x00, 0x03];
+    assert_eq!(lrc(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03]), 0x7E);
+    assert_eq!(encode_frame(0x11, &pdu), b":1103006B00037E\r\n");
+    assert_eq!(decode_frame(b"1103006B00037E"), Some((0x11, pdu.to_vec())));
+
+    // invalid LRC, hexadecimal or length
+    assert_eq!(decode_frame(b"1103006B00037F"), None);
+    assert_eq!(decode_frame(b"1103006B0003ZZ"), None);
+    assert_eq!(decode_frame(b"1103006B00037"), None);
+}
+
+#[tokio::test]
+async fn test_ascii_transport() {
+    let server = ModbusServer::new(HashMap::new(), registers());
+    server
+        .set_value("Energy", &RegisterValue::U32(123_456))
+        .unwrap();
+    let (device_end, server_end) = tokio::io::duplex(256);
+    server.serve_ascii(server_end, Slave(0x11));
+
+    let mut transport = AsciiTransport::new(device_end, Slave(0x11), Some(Duration::from_secs(1)));
+    let response = transport
+        .call(Request::ReadHoldingRegisters(0x6B, 2))
+        .await
+        .unwrap();
+    assert_eq!(
+        response,
+        Response::ReadHoldingRegisters(vec![0x0001, 0xE240])
+    );
+
+    transport
+        .call(Request::WriteMultipleRegisters(0x6B, vec![0, 42]))
+        .await
+        .unwrap();
+    assert_eq!(server.get_value("Energy").unwrap(), RegisterValue::U32(42));
+}
+
+#[tokio::test]
+async fn test_invalid_frames() {
+    let (device_end, mut line) = tokio::io::duplex(256);
+    tokio::spawn(async move {
+        let mut request = Vec::new();
+        while request.last() != Some(&b'\n') {
+            request.push(line.read_u8().await.unwrap());
+        }
+        let pdu = Response::ReadHoldingRegisters(vec![7]).encode();
+
+        // noise, a frame of another slave and a frame with a bad LRC
+        line.write_all(b"\x00\xFF").await.unwrap();
+        line.write_all(&encode_frame(0x12, &pdu)).await.unwrap();
+        line.write_all(b":1103020007FF\r\n").await.unwrap();
+        // frame interrupted for longer than the inter-character timeout
+        line.write_all(b":11030200").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        line.write_all(b"08E2\r\n").await.unwrap();
+
+        line.write_all(&encode_frame(0x11, &pdu)).await.unwrap();
+    });
+
+    let mut transport =
+        AsciiTransport::new(device_end, Slave(0x11), Some(Duration::from_millis(50)));
+    let response = transport
+        .call(Request::ReadHoldingRegisters(0, 1))
+        .await
+        .unwrap();
+    assert_eq!(response, Response::ReadHoldingRegisters(vec![7]));
+}
@@ -1,70 +1,76 @@
-use core::panic;
+use industrial_device::errors::IndustrialDeviceError;
+use industrial_device::types::Value;
+use modbus_device::errors::{ModbusError, RegisterError};
 use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
-use modbus_device::types::TCPContext;
+use modbus_device::pdu::{Request, Response};
+use modbus_device::register::{DataType, Register, WriteStrategy};
+use modbus_device::server::ModbusServer;
+use modbus_device::types::{ModBusRegisters, RegisterValue, TCPContext};
 use modbus_device::{utils, ModbusDeviceAsync};
+use std::collections::HashMap;
 use std::fs::File;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::thread;
-use std::time::Duration;
-use testcontainers::runners::AsyncRunner;
-use testcontainers::{core::WaitFor, ContainerAsync, GenericImage};
+use std::sync::{Arc, Mutex};
 use tokio;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
 
-fn create_server() -> GenericImage {
-    let server = GenericImage::new("modbus-test-server", "1")
-        .with_exposed_port(4502.into())
-        .with_wait_for(WaitFor::message_on_stdout("Started on port 4502"));
+fn create_server() -> ModbusServer {
+    let input_registers_json = File::open("tests/input_registers.json").unwrap();
+    let input_registers = utils::get_defs_from_json(input_registers_json).unwrap();
 
-    server
-}
+    let holding_registers_json = File::open("tests/holding_registers.json").unwrap();
+    let holding_registers = utils::get_defs_from_json(holding_registers_json).unwrap();
 
-async fn start_server(container: GenericImage) -> ContainerAsync<GenericImage> {
-    let server = container.start().await;
-    match server {
-        Ok(val) => Ok(val),
-        Err(err) => match &err {
-            testcontainers::TestcontainersError::Client(client_err) => match client_err {
-                testcontainers::core::error::ClientError::PullImage { descriptor: _, err: _pull_err } => panic!("Could not access docker image. Tests need the test server docker image. See https://github.com/lkzjdnb/modbus_device/blob/master/Testing.md \n {err}"),
-                _ => Err(err)
-            },
-            _ => Err(err),
-        },
-    }
-    .unwrap()
-}
+    let coils_json = File::open("tests/coils.json").unwrap();
+    let coils = utils::get_bit_defs_from_json(coils_json).unwrap();
 
-async fn connect(server: &ContainerAsync<GenericImage>) -> ModbusDeviceAsync {
-    let port = server.get_host_port_ipv4(4502_u16).await.unwrap();
+    ModbusServer::new(input_registers, holding_registers).with_coils(coils)
+}
 
-    let addr: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port);
+async fn start_server(server: &ModbusServer) -> SocketAddr {
+    server
+        .serve_tcp(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .await
+        .unwrap()
+}
 
+async fn connect(addr: SocketAddr) -> ModbusDeviceAsync {
     let input_registers_json = File::open("tests/input_registers.json").unwrap();
     let input_registers = utils::get_defs_from_json(input_registers_json).unwrap();
 
     let holding_registers_json = File::open("tests/holding_registers.json").unwrap();
     let holding_registers = utils::get_defs_from_json(holding_registers_json).unwrap();
 
+    let coils_json = File::open("tests/coils.json").unwrap();
+    let coils = utils::get_bit_defs_from_json(coils_json).unwrap();
+
     let device = ModbusDeviceAsync::new(
-        TCPContext { addr }.into(),
+        TCPContext::new(addr).into(),
         input_registers,
         holding_registers,
-    );
+    )
+    .with_coils(coils);
     device
 }
 
 #[tokio::test]
 async fn test_read() {
-    let container = create_server();
-    let server = start_server(container).await;
-    let mut device = connect(&server).await;
+    let server = create_server();
+    let addr = start_server(&server).await;
+    let mut device = connect(addr).await;
 
-    thread::sleep(Duration::from_secs(15));
     device.connect().await.unwrap();
 
-    device
+    server
+        .set_value("ProjectId", &RegisterValue::U32(0xDEADBEEF))
+        .unwrap();
+    let res = device
         .read_input_registers_by_name(&["ProjectId".to_string()])
         .await
         .unwrap();
+    assert_eq!(res["ProjectId"], RegisterValue::U32(0xDEADBEEF));
+
     device
         .read_holding_registers_by_name(&["Version".to_string()])
         .await
@@ -73,11 +79,10 @@ async fn test_read() {
 
 #[tokio::test]
 async fn test_write() {
-    let container = create_server();
-    let server = start_server(container).await;
-    let mut device = connect(&server).await;
+    let server = create_server();
+    let addr = start_server(&server).await;
+    let mut device = connect(addr).await;
 
-    thread::sleep(Duration::from_secs(15));
     device.connect().await.unwrap();
 
     let reg = device
@@ -88,4 +93,383 @@ async fn test_write() {
         .write_holding_register(&reg, &(0.52 as f32).into())
         .await
         .unwrap();
+
+    assert_eq!(
+        server.get_value("ProductionRate[%]").unwrap(),
+        RegisterValue::Float32(0.52)
+    );
+}
+
+#[tokio::test]
+async fn test_dump() {
+    let server = create_server();
+    let addr = start_server(&server).await;
+    let mut device = connect(addr).await;
+
+    device.connect().await.unwrap();
+
+    server
+        .set_value("Uptime[s]", &RegisterValue::U32(3600))
+        .unwrap();
+    let res = device.dump_input_registers().await.unwrap();
+    assert_eq!(res["Uptime[s]"], RegisterValue::U32(3600));
+}
+
+#[tokio::test]
+async fn test_coils() {
+    let server = create_server();
+    let addr = start_server(&server).await;
+    let mut device = connect(addr).await;
+
+    device.connect().await.unwrap();
+
+    device
+        .write_coil_by_name("Stop", &RegisterValue::Boolean(true))
+        .await
+        .unwrap();
+    assert_eq!(
+        server.get_value("Stop").unwrap(),
+        RegisterValue::Boolean(true)
+    );
+
+    let res = device.dump_coils().await.unwrap();
+    assert_eq!(res["Run"], RegisterValue::Boolean(false));
+    assert_eq!(res["Stop"], RegisterValue::Boolean(true));
+    assert_eq!(res["Reset"], RegisterValue::Boolean(false));
+}
+
+#[tokio::test]
+async fn test_read_detailed() {
+    let server = create_server();
+    let addr = start_server(&server).await;
+    let mut device = connect(addr).await;
+
+    device.connect().await.unwrap();
+
+    server
+        .set_value("ProjectId", &RegisterValue::U32(0xDEADBEEF))
+        .unwrap();
+    let res = device
+        .read_registers_by_name_detailed(
+            &["ProjectId".to_string(), "Missing".to_string()],
+            &ModBusRegisters::INPUT,
+        )
+        .await
+        .unwrap();
+    assert!(matches!(
+        res["ProjectId"],
+        Ok(RegisterValue::U32(0xDEADBEEF))
+    ));
+    assert!(matches!(
+        &res["Missing"],
+        Err(RegisterError::UnknownRegister { name }) if name == "Missing"
+    ));
+
+    let truncated = Register::new("Truncated", 2, 1, DataType::UInt32);
+    let unmapped = Register::new("Unmapped", 60000, 1, DataType::UInt16);
+    let res = device
+        .read_registers_detailed(&[truncated, unmapped], &ModBusRegisters::INPUT)
+        .await
+        .unwrap();
+    assert!(matches!(
+        &res["Truncated"],
+        Err(RegisterError::ConversionError { raw }) if raw == &vec![0]
+    ));
+    assert!(matches!(
+        res["Unmapped"],
+        Err(RegisterError::Exception { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_atomic_writes() {
+    let server = create_server();
+    let addr = start_server(&server).await;
+    let mut device = connect(addr).await;
+
+    device.connect().await.unwrap();
+
+    server
+        .set_value("RunProofTest", &RegisterValue::U16(0x00F3))
+        .unwrap();
+    device
+        .mask_write_holding_register_by_name("RunProofTest", 0x00F0, 0x0005)
+        .await
+        .unwrap();
+    assert_eq!(
+        server.get_value("RunProofTest").unwrap(),
+        RegisterValue::U16(0x00F5)
+    );
+
+    server
+        .set_value("Preheat", &RegisterValue::Boolean(true))
+        .unwrap();
+    let res = device
+        .read_write_holding_registers_by_name(
+            "RunProofTest",
+            &RegisterValue::U16(1),
+            &[
+                "Preheat".to_string(),
+                "Flush".to_string(),
+                "RunProofTest".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+    assert_eq!(res["Preheat"], RegisterValue::Boolean(true));
+    assert_eq!(res["Flush"], RegisterValue::Boolean(false));
+    assert_eq!(res["RunProofTest"], RegisterValue::U16(1));
+}
+
+// fake device answering the register writes, records the function code of each request
+async fn start_recording_server() -> (SocketAddr, Arc<Mutex<Vec<u8>>>) {
+    let listener = TcpListener::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    let functions = Arc::new(Mutex::new(Vec::new()));
+    let log = functions.clone();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        loop {
+            let mut header = [0u8; 7];
+            if stream.read_exact(&mut header).await.is_err() {
+                break;
+            }
+            let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+            let mut pdu = vec![0u8; len - 1];
+            stream.read_exact(&mut pdu).await.unwrap();
+            log.lock().unwrap().push(pdu[0]);
+
+            let response = match Request::decode(&pdu).unwrap() {
+                Request::WriteSingleRegister(addr, value) => {
+                    Response::WriteSingleRegister(addr, value)
+                }
+                Request::WriteMultipleRegisters(addr, values) => {
+                    Response::WriteMultipleRegisters(addr, values.len() as u16)
+                }
+                Request::ReadWriteMultipleRegisters(_, nb, _, _) => {
+                    Response::ReadWriteMultipleRegisters(vec![0; usize::from(nb)])
+                }
+                request => panic!("unexpected request {request:?}"),
+            }
+            .encode();
+            let mut frame = header[0..4].to_vec();
+            frame.extend((response.len() as u16 + 1).to_be_bytes());
+            frame.push(header[6]);
+            frame.extend(response);
+            stream.write_all(&frame).await.unwrap();
+        }
+    });
+    (addr, functions)
+}
+
+#[tokio::test]
+async fn test_write_strategies() {
+    for (strategy, expected) in [
+        (WriteStrategy::Auto, vec![0x06, 0x10, 0x17]),
+        (WriteStrategy::Single, vec![0x06, 0x06, 0x06, 0x17]),
+        (WriteStrategy::Multiple, vec![0x10, 0x10, 0x17]),
+    ] {
+        let (addr, functions) = start_recording_server().await;
+        let mut device = connect(addr).await.with_write_strategy(strategy);
+
+        device.connect().await.unwrap();
+
+        device
+            .write_holding_register_by_name("RunProofTest", &RegisterValue::U16(7))
+            .await
+            .unwrap();
+        device
+            .write_holding_register_by_name(
+                "Heartbeat_ModbusTimeout_s",
+                &RegisterValue::U32(0x00010002),
+            )
+            .await
+            .unwrap();
+        // the read/write request doesn't depend on the strategy
+        device
+            .read_write_holding_registers_by_name(
+                "RunProofTest",
+                &RegisterValue::U16(8),
+                &["Heartbeat_ModbusTimeout_s".to_string()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(*functions.lock().unwrap(), expected, "{strategy:?}");
+    }
+}
+
+// nothing is written when the words of a register go past the last address
+#[tokio::test]
+async fn test_write_single_past_last_address() {
+    let (addr, functions) = start_recording_server().await;
+    let holding_registers = HashMap::from([(
+        "Total".to_string(),
+        Register::new("Total", 0xFFFF, 2, DataType::UInt32),
+    )]);
+    let mut device = ModbusDeviceAsync::new(
+        TCPContext::new(addr).into(),
+        HashMap::new(),
+        holding_registers,
+    )
+    .with_write_strategy(WriteStrategy::Single);
+    device.connect().await.unwrap();
+
+    let res = device
+        .write_holding_register_by_name("Total", &RegisterValue::U32(7))
+        .await;
+    assert!(matches!(res, Err(ModbusError::QuantityError)));
+    assert!(functions.lock().unwrap().is_empty());
+}
+
+// the strategy of the register map overrides the device one, the strategy of a register overrides the map one
+#[tokio::test]
+async fn test_write_strategies_from_json() {
+    let registers =
+        utils::get_defs_from_json(File::open("tests/write_strategy.json").unwrap()).unwrap();
+    let (addr, functions) = start_recording_server().await;
+    let mut device =
+        ModbusDeviceAsync::new(TCPContext::new(addr).into(), HashMap::new(), registers)
+            .with_write_strategy(WriteStrategy::Multiple);
+
+    device.connect().await.unwrap();
+
+    for (name, val) in [
+        ("Speed", RegisterValue::U16(1)),
+        ("Counter", RegisterValue::U32(2)),
+        ("Total", RegisterValue::U32(3)),
+    ] {
+        device
+            .write_holding_register_by_name(name, &val)
+            .await
+            .unwrap();
+    }
+    assert_eq!(*functions.lock().unwrap(), [0x06, 0x10, 0x06, 0x06]);
+}
+
+// the value must have the size of the register, it is not truncated or padded
+#[tokio::test]
+async fn test_write_size_mismatch() {
+    let server = create_server();
+    let addr = start_server(&server).await;
+    let mut device = connect(addr).await;
+
+    device.connect().await.unwrap();
+
+    let res = device
+        .write_holding_register_by_name("RunProofTest", &RegisterValue::U32(7))
+        .await;
+    assert!(matches!(res, Err(ModbusError::ConversionError)));
+    let res = device
+        .write_holding_register_by_name("Heartbeat_ModbusTimeout_s", &RegisterValue::U16(7))
+        .await;
+    assert!(matches!(res, Err(ModbusError::ConversionError)));
+}
+
+// the values are converted to the type of the register when it can be done without loss
+#[tokio::test]
+async fn test_write_value_conversions() {
+    let server = create_server();
+    let addr = start_server(&server).await;
+    let mut device = connect(addr).await;
+
+    device.connect().await.unwrap();
+
+    // the trait is not imported, its connect would be ambiguous
+    industrial_device::IndustrialDevice::write_register_by_name(
+        &mut device,
+        "Heartbeat_ModbusTimeout_s",
+        &Value::U16(7),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        server.get_value("Heartbeat_ModbusTimeout_s").unwrap(),
+        RegisterValue::U32(7)
+    );
+    industrial_device::IndustrialDevice::write_register_by_name(
+        &mut device,
+        "RunProofTest",
+        &Value::U32(8),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        server.get_value("RunProofTest").unwrap(),
+        RegisterValue::U16(8)
+    );
+
+    // out of the range of the register
+    let res = industrial_device::IndustrialDevice::write_register_by_name(
+        &mut device,
+        "RunProofTest",
+        &Value::U32(70_000),
+    )
+    .await;
+    assert!(res.is_err());
+    assert_eq!(
+        server.get_value("RunProofTest").unwrap(),
+        RegisterValue::U16(8)
+    );
+}
+
+// the doubles are read as single precision floats, only when they are exact
+#[test]
+fn test_read_value_conversions() {
+    assert!(matches!(
+        Value::try_from(RegisterValue::Float64(0.5)),
+        Ok(Value::Float32(val)) if val == 0.5
+    ));
+    assert!(Value::try_from(RegisterValue::Float64(0.1)).is_err());
+    assert!(Value::try_from(RegisterValue::Float64(1e300)).is_err());
+    assert!(Value::try_from(RegisterValue::S64(i64::MAX)).is_err());
+}
+
+// the registers that can't be represented fail the dump instead of being left out
+#[tokio::test]
+async fn test_dump_unrepresentable() {
+    let registers = HashMap::from([
+        (
+            "Counter".to_string(),
+            Register::new("Counter", 0, 4, DataType::Int64),
+        ),
+        (
+            "Energy".to_string(),
+            Register::new("Energy", 4, 4, DataType::Float64),
+        ),
+    ]);
+    let server = ModbusServer::new(registers.clone(), HashMap::new());
+    let addr = start_server(&server).await;
+    let mut device =
+        ModbusDeviceAsync::new(TCPContext::new(addr).into(), registers, HashMap::new());
+    device.connect().await.unwrap();
+
+    server.set_value("Counter", &RegisterValue::S64(7)).unwrap();
+    server
+        .set_value("Energy", &RegisterValue::Float64(0.5))
+        .unwrap();
+    let res = industrial_device::IndustrialDevice::dump_registers(&mut device)
+        .await
+        .unwrap();
+    assert!(matches!(res["Counter"], Value::S32(7)));
+
+    server
+        .set_value("Counter", &RegisterValue::S64(i64::MAX))
+        .unwrap();
+    server
+        .set_value("Energy", &RegisterValue::Float64(0.1))
+        .unwrap();
+    let res = industrial_device::IndustrialDevice::dump_registers(&mut device).await;
+    assert!(matches!(
+        res,
+        Err(IndustrialDeviceError::ConversionError { .. })
+    ));
+    let err = ModbusError::UnrepresentableValueError {
+        names: "Counter, Energy".to_string(),
+    };
+    assert_eq!(
+        err.to_string(),
+        "Registers Counter, Energy can't be represented as values"
+    );
 }
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::register::{DataType, Register};
+use modbus_device::server::ModbusServer;
+use modbus_device::types::{BitWriteMode, RegisterValue, TCPContext};
+use modbus_device::ModbusDeviceAsync;
+
+fn register(name: &str, data_type: DataType, mask: Option<u16>) -> (String, Register) {
+    (
+        name.to_string(),
+        Register {
+            mask,
+            ..Register::new(name, 10, 1, data_type)
+        },
+    )
+}
+
+// status word with two alarm flags and a 4 bits mode
+fn status_registers() -> HashMap<String, Register> {
+    HashMap::from([
+        register("Status", DataType::UInt16, None),
+        register("Overheat", DataType::Boolean, Some(0x0001)),
+        register("Overload", DataType::Boolean, Some(0x0008)),
+        register("Mode", DataType::UInt16, Some(0x0F00)),
+    ])
+}
+
+async fn setup(mode: BitWriteMode) -> (ModbusServer, ModbusDeviceAsync) {
+    let server = ModbusServer::new(HashMap::new(), status_registers());
+    let addr = server
+        .serve_tcp(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .await
+        .unwrap();
+
+    let mut device = ModbusDeviceAsync::new(
+        TCPContext::new(addr).into(),
+        HashMap::new(),
+        status_registers(),
+    )
+    .with_bit_write_mode(mode);
+    device.connect().await.unwrap();
+    (server, device)
+}
+
+#[tokio::test]
+async fn test_read_bits() {
+    let (server, mut device) = setup(BitWriteMode::default()).await;
+
+    server
+        .set_value("Status", &RegisterValue::U16(0x0508))
+        .unwrap();
+    let res = device.dump_holding_registers().await.unwrap();
+    assert_eq!(res["Status"], RegisterValue::U16(0x0508));
+    assert_eq!(res["Overheat"], RegisterValue::Boolean(false));
+    assert_eq!(res["Overload"], RegisterValue::Boolean(true));
+    assert_eq!(res["Mode"], RegisterValue::U16(5));
+}
+
+#[tokio::test]
+async fn test_write_bits() {
+    for mode in [BitWriteMode::ReadModifyWrite, BitWriteMode::MaskWrite] {
+        let (server, mut device) = setup(mode).await;
+
+        server
+            .set_value("Status", &RegisterValue::U16(0x0508))
+            .unwrap();
+        device
+            .write_holding_register_by_name("Overheat", &RegisterValue::Boolean(true))
+            .await
+            .unwrap();
+        device
+            .write_holding_register_by_name("Overload", &RegisterValue::Boolean(false))
+            .await
+            .unwrap();
+        device
+            .write_holding_register_by_name("Mode", &RegisterValue::U16(2))
+            .await
+            .unwrap();
+        assert_eq!(
+            server.get_value("Status").unwrap(),
+            RegisterValue::U16(0x0201),
+            "{mode:?}"
+        );
+
+        // the value must fit in the mask
+        assert!(device
+            .write_holding_register_by_name("Mode", &RegisterValue::U16(16))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use modbus_device::errors::ModbusError;
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::register::{DataType, Register};
+use modbus_device::server::ModbusServer;
+use modbus_device::types::{ModBusRegisters, RegisterValue, TCPContext};
+use modbus_device::ModbusDeviceAsync;
+
+fn bits(names: &[&str], first: u16) -> HashMap<String, Register> {
+    names
+        .iter()
+        .zip(first..)
+        .map(|(name, addr)| {
+            (
+                name.to_string(),
+                Register::new(name, addr, 1, DataType::Boolean),
+            )
+        })
+        .collect()
+}
+
+fn coils() -> HashMap<String, Register> {
+    bits(&["Run", "Stop", "Reset"], 0)
+}
+
+fn discrete_inputs() -> HashMap<String, Register> {
+    bits(&["DoorOpen", "Overheat"], 10)
+}
+
+async fn setup() -> (ModbusServer, ModbusDeviceAsync) {
+    let server = ModbusServer::new(HashMap::new(), HashMap::new())
+        .with_coils(coils())
+        .with_discrete_inputs(discrete_inputs());
+    let addr = server
+        .serve_tcp(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .await
+        .unwrap();
+
+    let mut device =
+        ModbusDeviceAsync::new(TCPContext::new(addr).into(), HashMap::new(), HashMap::new())
+            .with_coils(coils())
+            .with_discrete_inputs(discrete_inputs());
+    device.connect().await.unwrap();
+    (server, device)
+}
+
+#[tokio::test]
+async fn test_read_coils() {
+    let (server, mut device) = setup().await;
+    server
+        .set_value("Stop", &RegisterValue::Boolean(true))
+        .unwrap();
+
+    let res = device
+        .read_coils_by_name(&["Run".to_string(), "Stop".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(res["Run"], RegisterValue::Boolean(false));
+    assert_eq!(res["Stop"], RegisterValue::Boolean(true));
+
+    let res = device.read_raw_bits(&0, &3, &ModBusRegisters::COIL).await;
+    assert_eq!(res.unwrap(), [false, true, false]);
+}
+
+#[tokio::test]
+async fn test_write_coils() {
+    let (server, mut device) = setup().await;
+
+    // write single coil (FC5)
+    device
+        .write_coil_by_name("Reset", &RegisterValue::Boolean(true))
+        .await
+        .unwrap();
+    assert_eq!(
+        server.get_value("Reset").unwrap(),
+        RegisterValue::Boolean(true)
+    );
+
+    // write multiple coils (FC15)
+    device.write_raw_coils(&0, &[true, true]).await.unwrap();
+    let res = device.dump_coils().await.unwrap();
+    assert_eq!(res["Run"], RegisterValue::Boolean(true));
+    assert_eq!(res["Stop"], RegisterValue::Boolean(true));
+    assert_eq!(res["Reset"], RegisterValue::Boolean(true));
+
+    // the coils only hold booleans
+    let res = device
+        .write_coil_by_name("Run", &RegisterValue::U16(1))
+        .await;
+    assert!(matches!(res, Err(ModbusError::ConversionError)));
+}
+
+#[tokio::test]
+async fn test_read_discrete_inputs() {
+    let (server, mut device) = setup().await;
+    server
+        .set_value("Overheat", &RegisterValue::Boolean(true))
+        .unwrap();
+
+    let res = device
+        .read_discrete_inputs_by_name(&["Overheat".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(res["Overheat"], RegisterValue::Boolean(true));
+
+    let res = device.dump_discrete_inputs().await.unwrap();
+    assert_eq!(res["DoorOpen"], RegisterValue::Boolean(false));
+    assert_eq!(res["Overheat"], RegisterValue::Boolean(true));
+
+    let res = device
+        .read_raw_bits(&10, &2, &ModBusRegisters::DISCRETE)
+        .await;
+    assert_eq!(res.unwrap(), [false, true]);
+
+    // the discrete inputs are read only
+    let res = device
+        .write_coil_by_name("DoorOpen", &RegisterValue::Boolean(true))
+        .await;
+    assert!(matches!(
+        res,
+        Err(ModbusError::RegisterDoesNotExistError { .. })
+    ));
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use modbus_device::errors::ModbusError;
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::pdu::{Request, Response};
+use modbus_device::reconnect::ConnectionState;
+use modbus_device::register::{DataType, Register};
+use modbus_device::types::{RTUContext, RegisterValue, TCPContext};
+use modbus_device::ModbusDeviceAsync;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_modbus::Slave;
+use tokio_serial::{DataBits, FlowControl, Parity, StopBits};
+
+fn registers() -> HashMap<String, Register> {
+    HashMap::from([(
+        "UnitId".to_string(),
+        Register::new("UnitId", 0, 1, DataType::UInt16),
+    )])
+}
+
+// fake TCP to RTU gateway, the registers of each slave hold its unit identifier
+async fn start_gateway() -> SocketAddr {
+    let listener = TcpListener::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        loop {
+            let mut header = [0u8; 7];
+            if stream.read_exact(&mut header).await.is_err() {
+                break;
+            }
+            let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+            let mut pdu = vec![0u8; len - 1];
+            stream.read_exact(&mut pdu).await.unwrap();
+
+            let Ok(Request::ReadInputRegisters(_, nb)) = Request::decode(&pdu) else {
+                panic!("unexpected request {pdu:?}");
+            };
+            let words = vec![header[6].into(); usize::from(nb)];
+            let response = Response::ReadInputRegisters(words).encode();
+            let mut frame = header[0..4].to_vec();
+            frame.extend((response.len() as u16 + 1).to_be_bytes());
+            frame.push(header[6]);
+            frame.extend(response);
+            stream.write_all(&frame).await.unwrap();
+        }
+    });
+    addr
+}
+
+// one connection reaches several slaves behind the gateway
+#[tokio::test]
+async fn test_unit_id() {
+    let addr = start_gateway().await;
+    let mut ctx = TCPContext::new(addr);
+    ctx.slave = Slave(3);
+    let mut device = ModbusDeviceAsync::new(ctx.into(), registers(), HashMap::new());
+    device.connect().await.unwrap();
+
+    let name = ["UnitId".to_string()];
+    let res = device.read_input_registers_by_name(&name).await.unwrap();
+    assert_eq!(res["UnitId"], RegisterValue::U16(3));
+
+    device.set_slave(Slave(4));
+    let res = device.read_input_registers_by_name(&name).await.unwrap();
+    assert_eq!(res["UnitId"], RegisterValue::U16(4));
+}
+
+// the serial line defaults to 8E1 as required by the specification
+#[test]
+fn test_serial_line_defaults() {
+    let ctx = RTUContext::new("/dev/ttyUSB0".to_string(), Slave(1), 19200);
+    assert_eq!(ctx.parity, Parity::Even);
+    assert_eq!(ctx.stop_bits, StopBits::One);
+    assert_eq!(ctx.data_bits, DataBits::Eight);
+    assert_eq!(ctx.flow_control, FlowControl::None);
+}
+
+// a missing serial port fails the connection instead of panicking
+#[tokio::test]
+async fn test_missing_serial_port() {
+    let mut ctx = RTUContext::new("/dev/does-not-exist".to_string(), Slave(1), 9600);
+    ctx.parity = Parity::Odd;
+    let mut device = ModbusDeviceAsync::new(ctx.into(), registers(), HashMap::new());
+    let state = device.connection_state();
+
+    let res = device.connect().await;
+    assert!(matches!(res, Err(ModbusError::SerialError { .. })));
+    assert_eq!(*state.borrow(), ConnectionState::Failed);
+}
@@ -0,0 +1,71 @@
+use modbus_device::deadband::{ChangeFilter, Deadband};
+use modbus_device::types::{ModBusRegisters, RegisterValue};
+
+const INPUT: &ModBusRegisters = &ModBusRegisters::INPUT;
+
+#[test]
+fn test_changes() {
+    let mut filter = ChangeFilter::new();
+    assert!(filter.update(INPUT, "State", &RegisterValue::U16(1)));
+    assert!(!filter.update(INPUT, "State", &RegisterValue::U16(1)));
+    assert!(filter.update(INPUT, "State", &RegisterValue::U16(2)));
+    assert!(filter.update(INPUT, "Name", &RegisterValue::String("Pump".to_string())));
+    assert!(!filter.update(INPUT, "Name", &RegisterValue::String("Pump".to_string())));
+
+    filter.reset(INPUT, "State");
+    assert!(filter.update(INPUT, "State", &RegisterValue::U16(2)));
+}
+
+#[test]
+fn test_absolute_deadband() {
+    let mut filter = ChangeFilter::new().with_deadband(
+        ModBusRegisters::INPUT,
+        "Temperature",
+        Deadband::Absolute(0.5),
+    );
+    assert!(filter.update(INPUT, "Temperature", &RegisterValue::Float64(20.0)));
+    assert!(!filter.update(INPUT, "Temperature", &RegisterValue::Float64(20.4)));
+    // the changes are measured from the last value reported
+    assert!(filter.update(INPUT, "Temperature", &RegisterValue::Float64(20.6)));
+    assert!(!filter.update(INPUT, "Temperature", &RegisterValue::Float64(20.2)));
+    assert!(filter.update(INPUT, "Temperature", &RegisterValue::Float64(19.9)));
+}
+
+#[test]
+fn test_percent_deadband() {
+    let mut filter =
+        ChangeFilter::new().with_deadband(ModBusRegisters::INPUT, "Flow", Deadband::Percent(10.0));
+    assert!(filter.update(INPUT, "Flow", &RegisterValue::U32(200)));
+    assert!(!filter.update(INPUT, "Flow", &RegisterValue::U32(215)));
+    assert!(filter.update(INPUT, "Flow", &RegisterValue::U32(221)));
+
+    // the values that are not numbers are compared
+    let mut filter = ChangeFilter::new().with_deadband(
+        ModBusRegisters::INPUT,
+        "Running",
+        Deadband::Percent(10.0),
+    );
+    assert!(filter.update(INPUT, "Running", &RegisterValue::Boolean(false)));
+    assert!(filter.update(INPUT, "Running", &RegisterValue::Boolean(true)));
+}
+
+// the holding and input registers with the same name are filtered separately
+#[test]
+fn test_same_name() {
+    let holding = &ModBusRegisters::HOLDING;
+    let mut filter = ChangeFilter::new().with_deadband(
+        ModBusRegisters::INPUT,
+        "Speed",
+        Deadband::Absolute(10.0),
+    );
+    assert!(filter.update(INPUT, "Speed", &RegisterValue::U16(100)));
+    assert!(filter.update(holding, "Speed", &RegisterValue::U16(105)));
+    assert!(!filter.update(holding, "Speed", &RegisterValue::U16(105)));
+    // no deadband for the holding register
+    assert!(filter.update(holding, "Speed", &RegisterValue::U16(106)));
+    assert!(!filter.update(INPUT, "Speed", &RegisterValue::U16(106)));
+
+    filter.reset(holding, "Speed");
+    assert!(filter.update(holding, "Speed", &RegisterValue::U16(106)));
+    assert!(!filter.update(INPUT, "Speed", &RegisterValue::U16(106)));
+}
@@ -0,0 +1,98 @@
+use modbus_device::register::{DataType, Endianness};
+use modbus_device::types::RegisterValue;
+
+const ORDERINGS: [Endianness; 4] = [
+    Endianness::ABCD,
+    Endianness::CDAB,
+    Endianness::BADC,
+    Endianness::DCBA,
+];
+
+fn round_trip(val: &RegisterValue, kind: DataType, endianness: Endianness) -> RegisterValue {
+    let words = val.to_words(endianness).unwrap();
+    (words, kind, endianness).try_into().unwrap()
+}
+
+#[test]
+fn test_wire_order() {
+    let val = RegisterValue::U32(0x11223344);
+    let expected: [(Endianness, Vec<u16>); 4] = [
+        (Endianness::ABCD, vec![0x1122, 0x3344]),
+        (Endianness::CDAB, vec![0x3344, 0x1122]),
+        (Endianness::BADC, vec![0x2211, 0x4433]),
+        (Endianness::DCBA, vec![0x4433, 0x2211]),
+    ];
+
+    for (endianness, words) in expected {
+        assert_eq!(val.to_words(endianness).unwrap(), words);
+        let decoded: RegisterValue = (words, DataType::UInt32, endianness).try_into().unwrap();
+        assert_eq!(decoded, val);
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let values = [
+        (RegisterValue::U16(0xBEEF), DataType::UInt16),
+        (RegisterValue::U32(0xDEADBEEF), DataType::UInt32),
+        (RegisterValue::U64(0x0102030405060708), DataType::UInt64),
+        (RegisterValue::U128(u128::MAX - 42), DataType::UInt128),
+        (RegisterValue::S32(-123456), DataType::Int32),
+        (RegisterValue::Enum16(3), DataType::Enum16),
+        (RegisterValue::Float32(0.52), DataType::Float32),
+    ];
+
+    for endianness in ORDERINGS {
+        for (val, kind) in &values {
+            assert_eq!(round_trip(val, *kind, endianness), *val, "{endianness:?}");
+        }
+    }
+}
+
+#[test]
+fn test_default_is_abcd() {
+    let val = RegisterValue::U64(0x0102030405060708);
+    let words: Vec<u16> = val.clone().try_into().unwrap();
+    assert_eq!(words, vec![0x0102, 0x0304, 0x0506, 0x0708]);
+
+    let decoded: RegisterValue = (words, DataType::UInt64).try_into().unwrap();
+    assert_eq!(decoded, val);
+}
+
+#[test]
+fn test_signed_and_double_round_trip() {
+    let values = [
+        (RegisterValue::S16(-1234), DataType::Int16, 1),
+        (RegisterValue::S64(-1_234_567_890_123), DataType::Int64, 4),
+        (RegisterValue::Float64(-1234.5678), DataType::Float64, 4),
+    ];
+
+    for (val, kind, len) in values {
+        for endianness in ORDERINGS {
+            assert_eq!(val.to_words(endianness).unwrap().len(), len);
+            assert_eq!(round_trip(&val, kind, endianness), val);
+        }
+    }
+    assert_eq!(
+        RegisterValue::S16(-2).to_words(Endianness::ABCD).unwrap(),
+        vec![0xFFFE]
+    );
+}
+
+// the words read must match the size of the type
+#[test]
+fn test_wrong_word_count() {
+    let kinds = [
+        DataType::UInt16,
+        DataType::Int16,
+        DataType::Enum16,
+        DataType::Boolean,
+        DataType::UInt32,
+    ];
+    for kind in kinds {
+        for words in [vec![], vec![1, 2, 3]] {
+            let res: Result<RegisterValue, _> = (words, kind, Endianness::ABCD).try_into();
+            assert!(res.is_err(), "{kind:?}");
+        }
+    }
+}
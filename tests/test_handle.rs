@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use modbus_device::handle::{DeviceHandle, Priority};
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::register::{DataType, Register};
+use modbus_device::server::ModbusServer;
+use modbus_device::types::{ModBusRegisters, RegisterValue, TCPContext};
+use modbus_device::ModbusDeviceAsync;
+use tokio::sync::oneshot;
+
+fn register(name: &str, addr: u16) -> (String, Register) {
+    (
+        name.to_string(),
+        Register::new(name, addr, 1, DataType::UInt16),
+    )
+}
+
+fn registers() -> HashMap<String, Register> {
+    HashMap::from([register("Setpoint", 0), register("Speed", 1)])
+}
+
+async fn setup() -> (ModbusServer, DeviceHandle) {
+    let server = ModbusServer::new(HashMap::new(), registers());
+    let addr = server
+        .serve_tcp(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .await
+        .unwrap();
+
+    let mut device =
+        ModbusDeviceAsync::new(TCPContext::new(addr).into(), HashMap::new(), registers());
+    device.connect().await.unwrap();
+    (server, DeviceHandle::new(device))
+}
+
+// keeps the device busy until the sender is used
+fn block(handle: &DeviceHandle) -> oneshot::Sender<()> {
+    let (tx, rx) = oneshot::channel::<()>();
+    let handle = handle.clone();
+    tokio::spawn(async move {
+        handle
+            .run(|_| {
+                Box::pin(async move {
+                    let _ = rx.await;
+                    Ok(())
+                })
+            })
+            .await
+    });
+    tx
+}
+
+// request recording the order in which the requests are run
+fn record(
+    handle: &DeviceHandle,
+    log: &Arc<Mutex<Vec<&'static str>>>,
+    name: &'static str,
+) -> tokio::task::JoinHandle<Result<(), modbus_device::errors::ModbusError>> {
+    let handle = handle.clone();
+    let log = log.clone();
+    tokio::spawn(async move {
+        handle
+            .run(move |_| {
+                Box::pin(async move {
+                    log.lock().unwrap().push(name);
+                    Ok(())
+                })
+            })
+            .await
+    })
+}
+
+#[tokio::test]
+async fn test_concurrent_access() {
+    let (server, handle) = setup().await;
+
+    let tasks: Vec<_> = (0..10u16)
+        .map(|i| {
+            let handle = handle.clone();
+            tokio::spawn(async move {
+                handle
+                    .write_holding_register_by_name("Speed", RegisterValue::U16(i))
+                    .await
+                    .unwrap();
+                handle
+                    .read_registers_by_name(&["Speed".to_string()], ModBusRegisters::HOLDING)
+                    .await
+                    .unwrap()
+            })
+        })
+        .collect();
+    for task in tasks {
+        assert!(task.await.unwrap().contains_key("Speed"));
+    }
+
+    handle
+        .write_holding_register_by_name("Setpoint", RegisterValue::U16(42))
+        .await
+        .unwrap();
+    assert_eq!(
+        server.get_value("Setpoint").unwrap(),
+        RegisterValue::U16(42)
+    );
+}
+
+#[tokio::test]
+async fn test_priorities() {
+    let (_server, handle) = setup().await;
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let release = block(&handle);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let low = handle.with_priority(Priority::Low);
+    let high = handle.with_priority(Priority::High);
+    let tasks = [
+        record(&low, &log, "poll 1"),
+        record(&handle, &log, "read"),
+        record(&low, &log, "poll 2"),
+        record(&high, &log, "write"),
+    ];
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    release.send(()).unwrap();
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+    assert_eq!(*log.lock().unwrap(), ["write", "read", "poll 1", "poll 2"]);
+}
+
+#[tokio::test]
+async fn test_cancel() {
+    let (_server, handle) = setup().await;
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let release = block(&handle);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let cancelled = record(&handle, &log, "cancelled");
+    let kept = record(&handle, &log, "kept");
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // the queued request is dropped with its future
+    cancelled.abort();
+    assert!(cancelled.await.unwrap_err().is_cancelled());
+    release.send(()).unwrap();
+    kept.await.unwrap().unwrap();
+    assert_eq!(*log.lock().unwrap(), ["kept"]);
+}
@@ -0,0 +1,133 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use modbus_device::identification::{DeviceIdCode, DeviceIdentification};
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::pdu::{Request, Response};
+use modbus_device::server::ModbusServer;
+use modbus_device::types::{RTUOverTCPContext, TCPContext};
+use modbus_device::ModbusDeviceAsync;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_modbus::Slave;
+
+fn identification() -> DeviceIdentification {
+    DeviceIdentification {
+        vendor_name: Some("ACME".to_string()),
+        product_code: Some("PMP-200".to_string()),
+        revision: Some("v1.4.2".to_string()),
+        vendor_url: Some("https://acme.example".to_string()),
+        product_name: Some("Pump controller".to_string()),
+        model_name: Some("PMP-200-EU".to_string()),
+        user_application_name: None,
+        // too large for a single response
+        private_objects: BTreeMap::from([(0x80, vec![0xAA; 200]), (0x81, vec![0x55; 200])]),
+        conformity_level: 0x83,
+    }
+}
+
+async fn start_server() -> SocketAddr {
+    ModbusServer::new(HashMap::new(), HashMap::new())
+        .with_identification(&identification())
+        .serve_tcp(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .await
+        .unwrap()
+}
+
+async fn setup() -> ModbusDeviceAsync {
+    let addr = start_server().await;
+    let mut device =
+        ModbusDeviceAsync::new(TCPContext::new(addr).into(), HashMap::new(), HashMap::new());
+    device.connect().await.unwrap();
+    device
+}
+
+#[tokio::test]
+async fn test_basic_identification() {
+    let mut device = setup().await;
+
+    let id = device
+        .read_device_identification(DeviceIdCode::Basic)
+        .await
+        .unwrap();
+    assert_eq!(
+        id,
+        DeviceIdentification {
+            vendor_name: Some("ACME".to_string()),
+            product_code: Some("PMP-200".to_string()),
+            revision: Some("v1.4.2".to_string()),
+            conformity_level: 0x83,
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_extended_identification() {
+    let mut device = setup().await;
+
+    // the private objects are sent in several parts
+    let id = device
+        .read_device_identification(DeviceIdCode::Extended)
+        .await
+        .unwrap();
+    assert_eq!(id, identification());
+}
+
+// the RTU responses are delimited by their objects
+#[tokio::test]
+async fn test_rtu_identification() {
+    // converter forwarding the RTU frames of the TCP connection to the serial device
+    let listener = TcpListener::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server =
+        ModbusServer::new(HashMap::new(), HashMap::new()).with_identification(&identification());
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        server.serve_rtu(stream, Slave(7));
+    });
+
+    let mut device = ModbusDeviceAsync::new(
+        RTUOverTCPContext::new(addr, Slave(7)).into(),
+        HashMap::new(),
+        HashMap::new(),
+    );
+    device.connect().await.unwrap();
+    let id = device
+        .read_device_identification(DeviceIdCode::Extended)
+        .await
+        .unwrap();
+    assert_eq!(id, identification());
+}
+
+// a stream access starting at an object of another category restarts at the first object
+#[tokio::test]
+async fn test_object_outside_category() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // the model name is a regular object, twice to check that the server still answers
+    let request = Request::ReadDeviceIdentification(DeviceIdCode::Basic as u8, 0x05);
+    for _ in 0..2 {
+        let pdu = request.encode();
+        let mut frame = vec![0, 1, 0, 0];
+        frame.extend((pdu.len() as u16 + 1).to_be_bytes());
+        frame.push(1);
+        frame.extend(pdu);
+        stream.write_all(&frame).await.unwrap();
+
+        let mut header = [0u8; 7];
+        stream.read_exact(&mut header).await.unwrap();
+        let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let mut pdu = vec![0u8; len - 1];
+        stream.read_exact(&mut pdu).await.unwrap();
+
+        let Ok(Response::ReadDeviceIdentification(part)) = Response::decode(&request, &pdu) else {
+            panic!("unexpected response {pdu:?}");
+        };
+        let ids: Vec<u8> = part.objects.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, [0x00, 0x01, 0x02]);
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use modbus_device::errors::ModbusError;
+use modbus_device::register::{DataType, Endianness, Register};
+use modbus_device::types::RegisterValue;
+
+fn state_register() -> Register {
+    Register {
+        labels: Some(HashMap::from([
+            (0, "Stopped".to_string()),
+            (1, "Running".to_string()),
+            (3, "Fault".to_string()),
+        ])),
+        ..Register::new("State", 0, 1, DataType::Enum16)
+    }
+}
+
+#[test]
+fn test_read_label() {
+    let reg = state_register();
+    assert_eq!(
+        reg.decode(vec![3], Endianness::ABCD).unwrap(),
+        RegisterValue::Label(3, "Fault".to_string())
+    );
+    // values without label are returned as is
+    assert_eq!(
+        reg.decode(vec![2], Endianness::ABCD).unwrap(),
+        RegisterValue::Enum16(2)
+    );
+}
+
+#[test]
+fn test_write_label() {
+    let reg = state_register();
+    assert_eq!(
+        reg.encode(
+            &RegisterValue::String("Running".to_string()),
+            Endianness::ABCD
+        )
+        .unwrap(),
+        vec![1]
+    );
+    assert_eq!(
+        reg.encode(
+            &RegisterValue::Label(3, "Fault".to_string()),
+            Endianness::ABCD
+        )
+        .unwrap(),
+        vec![3]
+    );
+    assert_eq!(
+        reg.encode(&RegisterValue::Enum16(2), Endianness::ABCD)
+            .unwrap(),
+        vec![2]
+    );
+    assert!(matches!(
+        reg.encode(&RegisterValue::String("Paused".to_string()), Endianness::ABCD),
+        Err(ModbusError::UnknownLabelError { label }) if label == "Paused"
+    ));
+}
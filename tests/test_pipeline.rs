@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::pdu::{Request, Response};
+use modbus_device::register::{DataType, Register};
+use modbus_device::server::ModbusServer;
+use modbus_device::types::{RegisterValue, TCPContext};
+use modbus_device::ModbusDeviceAsync;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_modbus::Slave;
+
+fn register(name: &str, addr: u16) -> (String, Register) {
+    (
+        name.to_string(),
+        Register::new(name, addr, 1, DataType::UInt16),
+    )
+}
+
+// registers far apart, read with one request each
+fn registers() -> HashMap<String, Register> {
+    HashMap::from([
+        register("Pressure", 0),
+        register("Flow", 100),
+        register("Level", 200),
+    ])
+}
+
+fn localhost() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)
+}
+
+#[tokio::test]
+async fn test_pipelined_read_write() {
+    let server = ModbusServer::new(registers(), registers());
+    let addr = server.serve_tcp(localhost()).await.unwrap();
+    for (name, val) in [("Pressure", 1), ("Flow", 2), ("Level", 3)] {
+        server.set_value(name, &RegisterValue::U16(val)).unwrap();
+    }
+
+    let mut device = ModbusDeviceAsync::new(TCPContext::new(addr).into(), registers(), registers())
+        .with_pipelining(2);
+    device.connect().await.unwrap();
+
+    let res = device.dump_input_registers().await.unwrap();
+    assert_eq!(res["Pressure"], RegisterValue::U16(1));
+    assert_eq!(res["Flow"], RegisterValue::U16(2));
+    assert_eq!(res["Level"], RegisterValue::U16(3));
+
+    device
+        .write_holding_register_by_name("Flow", &RegisterValue::U16(7))
+        .await
+        .unwrap();
+    assert_eq!(server.get_value("Flow").unwrap(), RegisterValue::U16(7));
+}
+
+// the responses are matched to their request even when they come in another order
+#[tokio::test]
+async fn test_out_of_order_responses() {
+    let listener = TcpListener::bind(localhost()).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        // answered only once the 3 requests are received
+        let mut frames = Vec::new();
+        for _ in 0..3 {
+            let mut header = [0u8; 7];
+            stream.read_exact(&mut header).await.unwrap();
+            let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+            let mut pdu = vec![0u8; len - 1];
+            stream.read_exact(&mut pdu).await.unwrap();
+            frames.push((header, Request::decode(&pdu).unwrap()));
+        }
+        for (header, request) in frames.into_iter().rev() {
+            let Request::ReadInputRegisters(addr, nb) = request else {
+                panic!("unexpected request {request:?}");
+            };
+            // each register holds its address
+            let response = Response::ReadInputRegisters((addr..addr + nb).collect()).encode();
+            let mut frame = header[0..4].to_vec();
+            frame.extend((response.len() as u16 + 1).to_be_bytes());
+            frame.push(header[6]);
+            frame.extend(response);
+            stream.write_all(&frame).await.unwrap();
+        }
+    });
+
+    let mut device =
+        ModbusDeviceAsync::new(TCPContext::new(addr).into(), registers(), HashMap::new())
+            .with_pipelining(3);
+    device.connect().await.unwrap();
+
+    let res = device.dump_input_registers().await.unwrap();
+    assert_eq!(res["Pressure"], RegisterValue::U16(0));
+    assert_eq!(res["Flow"], RegisterValue::U16(100));
+    assert_eq!(res["Level"], RegisterValue::U16(200));
+}
+
+// the slave changed after the connection applies to the pipelined reads
+#[tokio::test]
+async fn test_set_slave() {
+    let listener = TcpListener::bind(localhost()).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let slaves = Arc::new(Mutex::new(Vec::new()));
+    let log = slaves.clone();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        loop {
+            let mut header = [0u8; 7];
+            if stream.read_exact(&mut header).await.is_err() {
+                break;
+            }
+            let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+            let mut pdu = vec![0u8; len - 1];
+            stream.read_exact(&mut pdu).await.unwrap();
+            log.lock().unwrap().push(header[6]);
+
+            let Ok(Request::ReadInputRegisters(_, nb)) = Request::decode(&pdu) else {
+                panic!("unexpected request {pdu:?}");
+            };
+            let response = Response::ReadInputRegisters(vec![0; usize::from(nb)]).encode();
+            let mut frame = header[0..4].to_vec();
+            frame.extend((response.len() as u16 + 1).to_be_bytes());
+            frame.push(header[6]);
+            frame.extend(response);
+            stream.write_all(&frame).await.unwrap();
+        }
+    });
+
+    let mut device =
+        ModbusDeviceAsync::new(TCPContext::new(addr).into(), registers(), HashMap::new())
+            .with_pipelining(3);
+    device.connect().await.unwrap();
+
+    device.dump_input_registers().await.unwrap();
+    device.set_slave(Slave(5));
+    device.dump_input_registers().await.unwrap();
+    assert_eq!(*slaves.lock().unwrap(), [255, 255, 255, 5, 5, 5]);
+}
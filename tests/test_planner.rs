@@ -0,0 +1,72 @@
+use modbus_device::planner::ReadPlanner;
+use modbus_device::register::{DataType, Register};
+use modbus_device::types::ModBusRegisters;
+
+fn register(name: &str, addr: u16, len: u16) -> Register {
+    Register::new(name, addr, len, DataType::UInt16)
+}
+
+fn names(ranges: Vec<Vec<Register>>) -> Vec<Vec<String>> {
+    ranges
+        .into_iter()
+        .map(|range| range.into_iter().map(|r| r.name).collect())
+        .collect()
+}
+
+#[test]
+fn test_contiguous_only_by_default() {
+    let regs = [
+        register("c", 4, 1),
+        register("a", 0, 2),
+        register("b", 2, 1),
+    ];
+    let ranges = ReadPlanner::default().plan(&regs, &ModBusRegisters::INPUT);
+    assert_eq!(names(ranges), vec![vec!["a", "b"], vec!["c"]]);
+}
+
+#[test]
+fn test_merge_across_gaps() {
+    let regs = [
+        register("a", 0, 2),
+        register("b", 4, 1),
+        register("c", 10, 1),
+    ];
+    let planner = ReadPlanner {
+        max_gap: 2,
+        ..Default::default()
+    };
+    let ranges = planner.plan(&regs, &ModBusRegisters::INPUT);
+    assert_eq!(names(ranges), vec![vec!["a", "b"], vec!["c"]]);
+}
+
+#[test]
+fn test_max_read_len() {
+    let regs: Vec<Register> = (0..130).map(|i| register(&i.to_string(), i, 1)).collect();
+    let ranges = ReadPlanner::default().plan(&regs, &ModBusRegisters::HOLDING);
+    assert_eq!(ranges.len(), 2);
+    assert_eq!(ranges[0].len(), 125);
+
+    let planner = ReadPlanner {
+        max_read_len: 50,
+        ..Default::default()
+    };
+    let ranges = planner.plan(&regs, &ModBusRegisters::HOLDING);
+    assert_eq!(ranges.len(), 3);
+    assert!(ranges.iter().all(|range| range.len() <= 50));
+}
+
+#[test]
+fn test_forbidden_addresses() {
+    let regs = [register("a", 0, 2), register("b", 4, 1)];
+    let planner = ReadPlanner {
+        max_gap: 10,
+        forbidden: vec![(ModBusRegisters::INPUT, 3..4)],
+        ..Default::default()
+    };
+    let ranges = planner.plan(&regs, &ModBusRegisters::INPUT);
+    assert_eq!(names(ranges), vec![vec!["a"], vec!["b"]]);
+
+    // the holes of the other tables don't matter
+    let ranges = planner.plan(&regs, &ModBusRegisters::HOLDING);
+    assert_eq!(names(ranges), vec![vec!["a", "b"]]);
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use modbus_device::deadband::Deadband;
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::poller::Poller;
+use modbus_device::register::{DataType, Register};
+use modbus_device::server::ModbusServer;
+use modbus_device::types::{ModBusRegisters, RegisterValue, TCPContext};
+use modbus_device::ModbusDeviceAsync;
+
+fn register(name: &str, addr: u16) -> (String, Register) {
+    (
+        name.to_string(),
+        Register::new(name, addr, 1, DataType::UInt16),
+    )
+}
+
+fn registers() -> HashMap<String, Register> {
+    HashMap::from([
+        register("Pressure", 0),
+        register("Flow", 1),
+        register("Setpoint", 10),
+    ])
+}
+
+async fn setup() -> (ModbusServer, ModbusDeviceAsync) {
+    let server = ModbusServer::new(registers(), HashMap::new());
+    let addr = server
+        .serve_tcp(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .await
+        .unwrap();
+    server
+        .set_value("Pressure", &RegisterValue::U16(12))
+        .unwrap();
+
+    let mut device =
+        ModbusDeviceAsync::new(TCPContext::new(addr).into(), registers(), HashMap::new());
+    device.connect().await.unwrap();
+    (server, device)
+}
+
+#[tokio::test]
+async fn test_poll_intervals() {
+    let (_server, device) = setup().await;
+
+    let (poller, mut results) = Poller::new()
+        .with_input_registers(&["Pressure", "Flow"], Duration::from_millis(20))
+        .with_input_registers(&["Setpoint"], Duration::from_millis(500))
+        .spawn(device);
+
+    // both groups are due on the first tick and read together
+    let first = results.recv().await.unwrap();
+    assert_eq!(first.source, ModBusRegisters::INPUT);
+    let values = first.values.unwrap();
+    assert_eq!(values.len(), 3);
+    assert!(matches!(values["Pressure"], Ok(RegisterValue::U16(12))));
+
+    let mut previous = first.timestamp;
+    for _ in 0..5 {
+        let res = results.recv().await.unwrap();
+        let values = res.values.unwrap();
+        assert_eq!(values.len(), 2);
+        assert!(!values.contains_key("Setpoint"));
+        assert!(res.timestamp > previous);
+        previous = res.timestamp;
+    }
+
+    // the device is given back once stopped
+    let mut device = poller.stop().await.unwrap();
+    let res = device
+        .read_input_registers_by_name(&["Pressure".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(res["Pressure"], RegisterValue::U16(12));
+}
+
+#[tokio::test]
+async fn test_subscribe() {
+    let (server, device) = setup().await;
+
+    let (poller, mut changes) = Poller::new()
+        .with_input_registers(&["Pressure", "Flow"], Duration::from_millis(10))
+        .with_deadband(ModBusRegisters::INPUT, "Pressure", Deadband::Absolute(5.0))
+        .subscribe(device);
+
+    // the first values are always reported
+    let mut first = HashMap::new();
+    for _ in 0..2 {
+        let change = changes.recv().await.unwrap();
+        first.insert(change.name, change.value);
+    }
+    assert_eq!(first["Pressure"], RegisterValue::U16(12));
+    assert_eq!(first["Flow"], RegisterValue::U16(0));
+
+    // within the deadband
+    server
+        .set_value("Pressure", &RegisterValue::U16(15))
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    server.set_value("Flow", &RegisterValue::U16(1)).unwrap();
+    let change = changes.recv().await.unwrap();
+    assert_eq!(change.name, "Flow");
+    assert_eq!(change.value, RegisterValue::U16(1));
+
+    server
+        .set_value("Pressure", &RegisterValue::U16(18))
+        .unwrap();
+    let change = changes.recv().await.unwrap();
+    assert_eq!(change.name, "Pressure");
+    assert_eq!(change.value, RegisterValue::U16(18));
+
+    poller.stop().await.unwrap();
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::reconnect::{ConnectionState, ReconnectPolicy};
+use modbus_device::register::{DataType, Register};
+use modbus_device::server::ModbusServer;
+use modbus_device::types::{RegisterValue, TCPContext};
+use modbus_device::ModbusDeviceAsync;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{oneshot, watch};
+
+fn registers() -> HashMap<String, Register> {
+    HashMap::from([(
+        "Level".to_string(),
+        Register::new("Level", 0, 1, DataType::UInt16),
+    )])
+}
+
+fn localhost() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)
+}
+
+fn policy(max_attempts: Option<u32>) -> ReconnectPolicy {
+    ReconnectPolicy {
+        initial_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(50),
+        multiplier: 2.0,
+        max_attempts,
+        jitter: 0.0,
+    }
+}
+
+// forwards the connections to the server, the first one is closed when asked,
+// the connections are refused once `accepted` connections were forwarded
+async fn start_proxy(
+    server: SocketAddr,
+    accepted: usize,
+    close_first: oneshot::Receiver<()>,
+) -> SocketAddr {
+    let listener = TcpListener::bind(localhost()).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut close_first = Some(close_first);
+        for _ in 0..accepted {
+            let (mut client, _) = listener.accept().await.unwrap();
+            let mut upstream = TcpStream::connect(server).await.unwrap();
+            let close = close_first.take();
+            tokio::spawn(async move {
+                let forward = tokio::io::copy_bidirectional(&mut client, &mut upstream);
+                match close {
+                    Some(close) => tokio::select! {
+                        _ = forward => {}
+                        _ = close => {}
+                    },
+                    None => {
+                        let _ = forward.await;
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+// states published by the device, until it is connected again or has failed
+async fn transitions(mut state: watch::Receiver<ConnectionState>) -> Vec<ConnectionState> {
+    let mut states = Vec::new();
+    while state.changed().await.is_ok() {
+        let current = *state.borrow_and_update();
+        states.push(current);
+        if matches!(
+            current,
+            ConnectionState::Connected | ConnectionState::Failed
+        ) {
+            break;
+        }
+    }
+    states
+}
+
+#[test]
+fn test_backoff() {
+    let policy = policy(None);
+    let delays: Vec<_> = (1..=5).map(|attempt| policy.delay(attempt)).collect();
+    assert_eq!(
+        delays,
+        [10, 20, 40, 50, 50].map(Duration::from_millis).to_vec()
+    );
+    // the attempts start at 1
+    assert_eq!(policy.delay(0), Duration::from_millis(10));
+    // no overflow for the large attempts
+    assert_eq!(policy.delay(u32::MAX), Duration::from_millis(50));
+}
+
+#[test]
+fn test_jitter_bounds() {
+    let policy = ReconnectPolicy {
+        jitter: 0.1,
+        ..policy(None)
+    };
+    for attempt in 1..=100 {
+        let delay = policy.delay(attempt % 3 + 1);
+        let base = policy.initial_delay * 2u32.pow(attempt % 3);
+        assert!(
+            delay >= base.mul_f64(0.9) && delay <= base.mul_f64(1.1),
+            "{delay:?}"
+        );
+    }
+    // the jitter is clamped to the delay
+    let policy = ReconnectPolicy {
+        jitter: 5.0,
+        ..policy
+    };
+    for _ in 0..100 {
+        assert!(policy.delay(1) <= Duration::from_millis(20));
+    }
+}
+
+// the read is sent again once the lost connection is re-established
+#[tokio::test]
+async fn test_reconnect() {
+    let server = ModbusServer::new(registers(), HashMap::new());
+    let server_addr = server.serve_tcp(localhost()).await.unwrap();
+    server.set_value("Level", &RegisterValue::U16(7)).unwrap();
+
+    let (close, close_first) = oneshot::channel();
+    let addr = start_proxy(server_addr, 2, close_first).await;
+    let mut device =
+        ModbusDeviceAsync::new(TCPContext::new(addr).into(), registers(), HashMap::new())
+            .with_reconnect_policy(policy(Some(3)));
+    device.connect().await.unwrap();
+    let name = ["Level".to_string()];
+    device.read_input_registers_by_name(&name).await.unwrap();
+
+    let states = tokio::spawn(transitions(device.connection_state()));
+    close.send(()).unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let res = device.read_input_registers_by_name(&name).await.unwrap();
+    assert_eq!(res["Level"], RegisterValue::U16(7));
+    assert_eq!(
+        states.await.unwrap(),
+        [
+            ConnectionState::Reconnecting { attempt: 1 },
+            ConnectionState::Connected
+        ]
+    );
+}
+
+// the device gives up after the maximum number of attempts
+#[tokio::test]
+async fn test_reconnect_failed() {
+    let server = ModbusServer::new(registers(), HashMap::new());
+    let server_addr = server.serve_tcp(localhost()).await.unwrap();
+
+    let (close, close_first) = oneshot::channel();
+    let addr = start_proxy(server_addr, 1, close_first).await;
+    let mut device =
+        ModbusDeviceAsync::new(TCPContext::new(addr).into(), registers(), HashMap::new())
+            .with_reconnect_policy(policy(Some(3)));
+    device.connect().await.unwrap();
+
+    let states = tokio::spawn(transitions(device.connection_state()));
+    close.send(()).unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    assert!(device
+        .read_input_registers_by_name(&["Level".to_string()])
+        .await
+        .is_err());
+    assert_eq!(
+        states.await.unwrap(),
+        [
+            ConnectionState::Reconnecting { attempt: 1 },
+            ConnectionState::Reconnecting { attempt: 2 },
+            ConnectionState::Reconnecting { attempt: 3 },
+            ConnectionState::Failed
+        ]
+    );
+}
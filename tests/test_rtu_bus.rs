@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use modbus_device::errors::ModbusError;
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::pdu::{Request, Response};
+use modbus_device::register::{DataType, Register};
+use modbus_device::rtu_bus::RTUBus;
+use modbus_device::transport::Transport;
+use modbus_device::types::{RTUContext, RegisterValue};
+use modbus_device::ModbusDeviceAsync;
+use tokio::time::Instant;
+use tokio_modbus::Slave;
+use tokio_serial::{Parity, StopBits};
+
+// start and end of the transactions of each slave
+type Transactions = Arc<Mutex<Vec<(u8, Instant, Instant)>>>;
+
+// serial line with several slaves, each register holds the address of its slave
+#[derive(Debug)]
+struct FakeLine {
+    slave: Slave,
+    delays: HashMap<u8, Duration>, // time taken by each slave to answer
+    transactions: Transactions,
+}
+
+#[async_trait]
+impl Transport for FakeLine {
+    async fn call(&mut self, request: Request) -> Result<Response, ModbusError> {
+        let start = Instant::now();
+        let delay = self.delays.get(&self.slave.0).copied().unwrap_or_default();
+        tokio::time::sleep(delay).await;
+        self.transactions
+            .lock()
+            .unwrap()
+            .push((self.slave.0, start, Instant::now()));
+
+        let Request::ReadHoldingRegisters(_, nb) = request else {
+            panic!("unexpected request {request:?}");
+        };
+        Ok(Response::ReadHoldingRegisters(vec![
+            self.slave.0.into();
+            usize::from(nb)
+        ]))
+    }
+
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave = slave;
+    }
+}
+
+fn registers() -> HashMap<String, Register> {
+    HashMap::from([(
+        "Address".to_string(),
+        Register::new("Address", 0, 1, DataType::UInt16),
+    )])
+}
+
+fn bus(config: RTUContext, delays: HashMap<u8, Duration>) -> (RTUBus, Transactions) {
+    let transactions = Transactions::default();
+    let line = FakeLine {
+        slave: Slave(0),
+        delays,
+        transactions: transactions.clone(),
+    };
+    (RTUBus::from_transport(config, Box::new(line)), transactions)
+}
+
+fn config(speed: u32) -> RTUContext {
+    RTUContext::new("/dev/null".to_string(), Slave(0), speed)
+}
+
+async fn read_address(device: &mut ModbusDeviceAsync, slave: u16) {
+    for _ in 0..3 {
+        let res = device
+            .read_holding_registers_by_name(&["Address".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(res["Address"], RegisterValue::U16(slave));
+    }
+}
+
+#[test]
+fn test_frame_gap() {
+    // 11 bits per character (start, 8 data, parity and stop bits)
+    assert_eq!(
+        config(9600).frame_gap(),
+        Duration::from_secs_f64(38.5 / 9600.0)
+    );
+    let mut ctx = config(9600);
+    ctx.parity = Parity::None;
+    ctx.stop_bits = StopBits::Two;
+    assert_eq!(ctx.frame_gap(), Duration::from_secs_f64(38.5 / 9600.0));
+    ctx.stop_bits = StopBits::One;
+    assert_eq!(ctx.frame_gap(), Duration::from_secs_f64(35.0 / 9600.0));
+    // fixed above 19200 bauds
+    assert_eq!(
+        config(19200).frame_gap(),
+        Duration::from_secs_f64(38.5 / 19200.0)
+    );
+    assert_eq!(config(115200).frame_gap(), Duration::from_micros(1750));
+}
+
+// the transactions of the slaves don't overlap and are separated by the frame gap
+#[tokio::test]
+async fn test_slaves_take_turns() {
+    let delays = HashMap::from([
+        (1, Duration::from_millis(20)),
+        (2, Duration::from_millis(10)),
+    ]);
+    let (bus, transactions) = bus(config(9600), delays);
+    let mut drive = bus.device(Slave(1), HashMap::new(), registers());
+    let mut meter = bus.device(Slave(2), HashMap::new(), registers());
+    drive.connect().await.unwrap();
+    meter.connect().await.unwrap();
+
+    tokio::join!(read_address(&mut drive, 1), read_address(&mut meter, 2));
+
+    let mut transactions = transactions.lock().unwrap().clone();
+    assert_eq!(transactions.len(), 6);
+    transactions.sort_by_key(|(_, start, _)| *start);
+    for pair in transactions.windows(2) {
+        let (_, _, end) = pair[0];
+        let (_, start, _) = pair[1];
+        assert!(start >= end + config(9600).frame_gap());
+    }
+}
+
+// the response timeout doesn't include the wait for the transactions of the other slaves
+#[tokio::test]
+async fn test_timeout_after_line_acquired() {
+    let delays = HashMap::from([
+        (1, Duration::from_millis(150)),
+        (2, Duration::from_millis(100)),
+    ]);
+    let mut config = config(115200);
+    config.timeouts.response = Some(Duration::from_millis(200));
+    let (bus, _) = bus(config, delays);
+    let mut drive = bus.device(Slave(1), HashMap::new(), registers());
+    let mut meter = bus.device(Slave(2), HashMap::new(), registers());
+    drive.connect().await.unwrap();
+    meter.connect().await.unwrap();
+
+    let name = ["Address".to_string()];
+    let (drive_res, meter_res) = tokio::join!(drive.read_holding_registers_by_name(&name), async {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        meter.read_holding_registers_by_name(&name).await
+    });
+    assert_eq!(drive_res.unwrap()["Address"], RegisterValue::U16(1));
+    assert_eq!(meter_res.unwrap()["Address"], RegisterValue::U16(2));
+}
+
+// a slave that doesn't answer in time doesn't stop the other slaves of the line
+#[tokio::test]
+async fn test_timeout_keeps_line() {
+    let delays = HashMap::from([(1, Duration::from_millis(300))]);
+    let mut config = config(115200);
+    config.timeouts.response = Some(Duration::from_millis(100));
+    let (bus, _) = bus(config, delays);
+    let mut drive = bus.device(Slave(1), HashMap::new(), registers());
+    let mut meter = bus.device(Slave(2), HashMap::new(), registers());
+    drive.connect().await.unwrap();
+    meter.connect().await.unwrap();
+
+    let name = ["Address".to_string()];
+    let res = drive.read_holding_registers_by_name(&name).await;
+    assert!(matches!(res, Err(ModbusError::Timeout)));
+    let res = meter.read_holding_registers_by_name(&name).await.unwrap();
+    assert_eq!(res["Address"], RegisterValue::U16(2));
+    // the silent slave is still reached
+    let res = drive.read_holding_registers_by_name(&name).await;
+    assert!(matches!(res, Err(ModbusError::Timeout)));
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::register::{DataType, Register};
+use modbus_device::server::ModbusServer;
+use modbus_device::types::{RTUOverTCPContext, RegisterValue};
+use modbus_device::ModbusDeviceAsync;
+use tokio::net::TcpListener;
+use tokio_modbus::Slave;
+
+fn registers() -> HashMap<String, Register> {
+    HashMap::from([(
+        "Speed".to_string(),
+        Register::new("Speed", 3, 2, DataType::UInt32),
+    )])
+}
+
+#[tokio::test]
+async fn test_rtu_over_tcp() {
+    // converter forwarding the RTU frames of the TCP connection to the serial device
+    let listener = TcpListener::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = ModbusServer::new(HashMap::new(), registers());
+    let serial_device = server.clone();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        serial_device.serve_rtu(stream, Slave(7));
+    });
+
+    let mut device = ModbusDeviceAsync::new(
+        RTUOverTCPContext::new(addr, Slave(7)).into(),
+        HashMap::new(),
+        registers(),
+    );
+    device.connect().await.unwrap();
+
+    device
+        .write_holding_register_by_name("Speed", &RegisterValue::U32(70_000))
+        .await
+        .unwrap();
+    assert_eq!(
+        server.get_value("Speed").unwrap(),
+        RegisterValue::U32(70_000)
+    );
+    let res = device.dump_holding_registers().await.unwrap();
+    assert_eq!(res["Speed"], RegisterValue::U32(70_000));
+}
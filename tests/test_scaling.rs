@@ -0,0 +1,61 @@
+use modbus_device::register::{DataType, Endianness, Register};
+use modbus_device::types::RegisterValue;
+
+fn scaled_register(data_type: DataType, len: u16, scale: f64, offset: f64) -> Register {
+    Register {
+        scale: Some(scale),
+        offset: Some(offset),
+        unit: Some("°C".to_string()),
+        ..Register::new("Temperature[°C]", 0, len, data_type)
+    }
+}
+
+fn as_float(val: RegisterValue) -> f64 {
+    match val {
+        RegisterValue::Float64(val) => val,
+        val => panic!("{val:?} is not a scaled value"),
+    }
+}
+
+#[test]
+fn test_read_scaling() {
+    let reg = scaled_register(DataType::UInt16, 1, 0.1, -40.0);
+    let val = as_float(reg.decode(vec![635], Endianness::ABCD).unwrap());
+    assert!((val - 23.5).abs() < 1e-9);
+
+    let reg = scaled_register(DataType::Int32, 2, 0.01, 0.0);
+    let val = as_float(reg.decode(vec![0xFFFF, 0xFF9C], Endianness::ABCD).unwrap());
+    assert!((val + 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_write_scaling() {
+    let reg = scaled_register(DataType::UInt16, 1, 0.1, -40.0);
+    assert_eq!(
+        reg.encode(&RegisterValue::Float64(23.5), Endianness::ABCD)
+            .unwrap(),
+        vec![635]
+    );
+    // the raw value is rounded
+    assert_eq!(
+        reg.encode(&RegisterValue::Float32(23.54), Endianness::ABCD)
+            .unwrap(),
+        vec![635]
+    );
+    // out of the range of the raw type
+    assert!(reg
+        .encode(&RegisterValue::Float64(-50.0), Endianness::ABCD)
+        .is_err());
+    assert!(reg
+        .encode(&RegisterValue::Boolean(true), Endianness::ABCD)
+        .is_err());
+}
+
+#[test]
+fn test_unscaled() {
+    let reg = Register::new("Temperature[°C]", 0, 1, DataType::UInt16);
+    assert_eq!(
+        reg.decode(vec![635], Endianness::ABCD).unwrap(),
+        RegisterValue::U16(635)
+    );
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use modbus_device::errors::ModbusError;
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::pdu::{Request, ILLEGAL_DATA_VALUE};
+use modbus_device::register::{DataType, Register};
+use modbus_device::server::ModbusServer;
+use modbus_device::types::TCPContext;
+use modbus_device::ModbusDeviceAsync;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn registers() -> HashMap<String, Register> {
+    HashMap::from([(
+        "Level".to_string(),
+        Register::new("Level", 0, 1, DataType::UInt16),
+    )])
+}
+
+async fn start_server() -> SocketAddr {
+    ModbusServer::new(registers(), registers())
+        .serve_tcp(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .await
+        .unwrap()
+}
+
+// send the protocol data unit of a request as is and return the response one
+async fn send(stream: &mut TcpStream, pdu: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0, 1, 0, 0];
+    frame.extend((pdu.len() as u16 + 1).to_be_bytes());
+    frame.push(1);
+    frame.extend(pdu);
+    stream.write_all(&frame).await.unwrap();
+
+    let mut header = [0u8; 7];
+    stream.read_exact(&mut header).await.unwrap();
+    let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+    let mut pdu = vec![0u8; len - 1];
+    stream.read_exact(&mut pdu).await.unwrap();
+    pdu
+}
+
+// the quantities that don't fit in a protocol data unit are rejected with illegal data value
+#[tokio::test]
+async fn test_quantity_limits() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    let requests = [
+        (Request::ReadHoldingRegisters(0, 126), 0x03),
+        (Request::ReadInputRegisters(0, 0), 0x04),
+        (Request::ReadCoils(0, 2001), 0x01),
+        (Request::ReadDiscreteInputs(0, 2001), 0x02),
+        (Request::WriteMultipleRegisters(0, vec![0; 124]), 0x10),
+        (Request::WriteMultipleCoils(0, vec![false; 1969]), 0x0F),
+        (
+            Request::ReadWriteMultipleRegisters(0, 1, 0, vec![0; 122]),
+            0x17,
+        ),
+        (
+            Request::ReadWriteMultipleRegisters(0, 126, 0, vec![0]),
+            0x17,
+        ),
+    ];
+    for (request, function) in requests {
+        assert_eq!(
+            send(&mut stream, &request.encode()).await,
+            [function | 0x80, ILLEGAL_DATA_VALUE],
+            "{request:?}"
+        );
+    }
+
+    // the largest read is answered, it is then rejected for the address
+    let response = send(&mut stream, &Request::ReadHoldingRegisters(0, 125).encode()).await;
+    assert_eq!(response, [0x83, 0x02]);
+}
+
+// the requests that don't fit in a protocol data unit are not sent
+#[tokio::test]
+async fn test_request_too_large() {
+    let addr = start_server().await;
+    let mut device = ModbusDeviceAsync::new(TCPContext::new(addr).into(), registers(), registers());
+    device.connect().await.unwrap();
+
+    let res = device.write_raw_holding_registers(&0, &[0; 124]).await;
+    assert!(matches!(res, Err(ModbusError::QuantityError)));
+    let res = device.write_raw_coils(&0, &[false; 1969]).await;
+    assert!(matches!(res, Err(ModbusError::QuantityError)));
+}
+
+// the ranges going past the last address are not sent
+#[tokio::test]
+async fn test_range_past_last_address() {
+    let addr = start_server().await;
+    let registers = HashMap::from([(
+        "Total".to_string(),
+        Register::new("Total", 0xFFFF, 2, DataType::UInt32),
+    )]);
+    let mut device =
+        ModbusDeviceAsync::new(TCPContext::new(addr).into(), registers, HashMap::new());
+    device.connect().await.unwrap();
+
+    let res = device
+        .read_input_registers_by_name(&["Total".to_string()])
+        .await;
+    assert!(matches!(res, Err(ModbusError::QuantityError)));
+}
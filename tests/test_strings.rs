@@ -0,0 +1,144 @@
+use modbus_device::errors::RegisterError;
+use modbus_device::register::{DataType, Endianness, Register, StringEncoding, StringFormat};
+use modbus_device::types::RegisterValue;
+use modbus_device::utils;
+use std::fs::File;
+
+fn string_register(len: u16, format: StringFormat) -> Register {
+    Register::new("Name", 0, len, DataType::String(format))
+}
+
+#[test]
+fn test_padding_and_trim() {
+    let reg = string_register(4, StringFormat::default());
+    let words = reg
+        .encode(&RegisterValue::String("Pump".to_string()), Endianness::ABCD)
+        .unwrap();
+    assert_eq!(words, vec![0x5075, 0x6D70, 0x0000, 0x0000]);
+    assert_eq!(
+        reg.decode(words, Endianness::ABCD).unwrap(),
+        RegisterValue::String("Pump".to_string())
+    );
+
+    // space padding and garbage after the NUL terminator
+    let words = vec![0x2050, 0x756D, 0x7020, 0x0041];
+    assert_eq!(
+        reg.decode(words.clone(), Endianness::ABCD).unwrap(),
+        RegisterValue::String("Pump".to_string())
+    );
+
+    let raw = string_register(
+        4,
+        StringFormat {
+            trim: false,
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        raw.decode(words, Endianness::ABCD).unwrap(),
+        RegisterValue::String(" Pump \0A".to_string())
+    );
+}
+
+#[test]
+fn test_byte_swap() {
+    let reg = string_register(
+        2,
+        StringFormat {
+            byte_swap: true,
+            ..Default::default()
+        },
+    );
+    let words = reg
+        .encode(&RegisterValue::String("SN1".to_string()), Endianness::ABCD)
+        .unwrap();
+    assert_eq!(words, vec![0x4E53, 0x0031]);
+    assert_eq!(
+        reg.decode(words, Endianness::ABCD).unwrap(),
+        RegisterValue::String("SN1".to_string())
+    );
+}
+
+#[test]
+fn test_encodings() {
+    let latin1 = string_register(
+        2,
+        StringFormat {
+            encoding: StringEncoding::Latin1,
+            ..Default::default()
+        },
+    );
+    let words = latin1
+        .encode(&RegisterValue::String("Café".to_string()), Endianness::ABCD)
+        .unwrap();
+    assert_eq!(words, vec![0x4361, 0x66E9]);
+    assert_eq!(
+        latin1.decode(words.clone(), Endianness::ABCD).unwrap(),
+        RegisterValue::String("Café".to_string())
+    );
+
+    let ascii = string_register(
+        2,
+        StringFormat {
+            encoding: StringEncoding::Ascii,
+            ..Default::default()
+        },
+    );
+    assert!(matches!(
+        ascii.decode(words, Endianness::ABCD),
+        Err(RegisterError::ConversionError { .. })
+    ));
+    assert!(ascii
+        .encode(&RegisterValue::String("Café".to_string()), Endianness::ABCD)
+        .is_err());
+
+    let utf8 = string_register(3, StringFormat::default());
+    let words = utf8
+        .encode(&RegisterValue::String("Café".to_string()), Endianness::ABCD)
+        .unwrap();
+    assert_eq!(
+        utf8.decode(words, Endianness::ABCD).unwrap(),
+        RegisterValue::String("Café".to_string())
+    );
+}
+
+#[test]
+fn test_too_long() {
+    let reg = string_register(2, StringFormat::default());
+    assert!(reg
+        .encode(
+            &RegisterValue::String("Too long".to_string()),
+            Endianness::ABCD
+        )
+        .is_err());
+}
+
+// the legacy fixed size type is read as a string of the length of the register
+#[test]
+fn test_legacy_sized() {
+    let registers =
+        utils::get_defs_from_json(File::open("tests/legacy_strings.json").unwrap()).unwrap();
+    let reg = &registers["Name"];
+    assert!(matches!(reg.data_type, DataType::String(_)));
+    assert_eq!(reg.len, 4);
+    assert_eq!(
+        reg.decode(vec![0x5075, 0x6D70, 0, 0], Endianness::ABCD)
+            .unwrap(),
+        RegisterValue::String("Pump".to_string())
+    );
+}
+
+// an empty string has no word to write without the length of its register
+#[test]
+fn test_empty_string_words() {
+    assert!(RegisterValue::String(String::new())
+        .to_words(Endianness::ABCD)
+        .is_err());
+    // the register pads it
+    assert_eq!(
+        string_register(2, StringFormat::default())
+            .encode(&RegisterValue::String(String::new()), Endianness::ABCD)
+            .unwrap(),
+        [0, 0]
+    );
+}
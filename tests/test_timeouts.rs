@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use modbus_device::errors::ModbusError;
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::pdu::{Request, Response};
+use modbus_device::reconnect::ConnectionState;
+use modbus_device::register::{DataType, Register};
+use modbus_device::types::{RegisterValue, TCPContext};
+use modbus_device::ModbusDeviceAsync;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+fn registers() -> HashMap<String, Register> {
+    HashMap::from([(
+        "Level".to_string(),
+        Register::new("Level", 0, 1, DataType::UInt16),
+    )])
+}
+
+// fake device answering the first request late, each response holds the number of the request
+async fn start_slow_server(delay: Duration) -> SocketAddr {
+    let listener = TcpListener::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut count = 0;
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            loop {
+                let mut header = [0u8; 7];
+                if stream.read_exact(&mut header).await.is_err() {
+                    break;
+                }
+                let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+                let mut pdu = vec![0u8; len - 1];
+                stream.read_exact(&mut pdu).await.unwrap();
+                let Ok(Request::ReadInputRegisters(_, nb)) = Request::decode(&pdu) else {
+                    panic!("unexpected request {pdu:?}");
+                };
+
+                count += 1;
+                if count == 1 {
+                    tokio::time::sleep(delay).await;
+                }
+                let response = Response::ReadInputRegisters(vec![count; usize::from(nb)]).encode();
+                let mut frame = header[0..4].to_vec();
+                frame.extend((response.len() as u16 + 1).to_be_bytes());
+                frame.push(header[6]);
+                frame.extend(response);
+                if stream.write_all(&frame).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    addr
+}
+
+// the late response of a request is not taken for the response of the next one
+#[tokio::test]
+async fn test_late_response() {
+    let addr = start_slow_server(Duration::from_millis(300)).await;
+    let mut ctx = TCPContext::new(addr);
+    ctx.timeouts.response = Some(Duration::from_millis(100));
+    let mut device = ModbusDeviceAsync::new(ctx.into(), registers(), HashMap::new());
+    let state = device.connection_state();
+    device.connect().await.unwrap();
+
+    let res = device
+        .read_input_registers_by_name(&["Level".to_string()])
+        .await;
+    assert!(matches!(res, Err(ModbusError::Timeout)));
+    assert_eq!(*state.borrow(), ConnectionState::Disconnected);
+
+    // the connection was closed with the late response
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let res = device
+        .read_input_registers_by_name(&["Level".to_string()])
+        .await;
+    assert!(matches!(res, Err(ModbusError::DeviceNotConnectedError)));
+
+    device.connect().await.unwrap();
+    let res = device
+        .read_input_registers_by_name(&["Level".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(res["Level"], RegisterValue::U16(2));
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use modbus_device::errors::ModbusError;
+use modbus_device::modbus_connexion_async::ModbusConnexionAsync;
+use modbus_device::pdu::{Request, Response};
+use modbus_device::register::{DataType, Register};
+use modbus_device::server::ModbusServer;
+use modbus_device::types::{RegisterValue, UDPContext};
+use modbus_device::ModbusDeviceAsync;
+use tokio::net::UdpSocket;
+
+fn registers() -> HashMap<String, Register> {
+    HashMap::from([(
+        "Level".to_string(),
+        Register::new("Level", 5, 1, DataType::UInt16),
+    )])
+}
+
+fn localhost() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)
+}
+
+#[tokio::test]
+async fn test_udp() {
+    let server = ModbusServer::new(HashMap::new(), registers());
+    let addr = server.serve_udp(localhost()).await.unwrap();
+
+    let mut device =
+        ModbusDeviceAsync::new(UDPContext::new(addr).into(), HashMap::new(), registers());
+    device.connect().await.unwrap();
+
+    device
+        .write_holding_register_by_name("Level", &RegisterValue::U16(42))
+        .await
+        .unwrap();
+    assert_eq!(server.get_value("Level").unwrap(), RegisterValue::U16(42));
+    let res = device.dump_holding_registers().await.unwrap();
+    assert_eq!(res["Level"], RegisterValue::U16(42));
+}
+
+#[tokio::test]
+async fn test_retransmission() {
+    let socket = UdpSocket::bind(localhost()).await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 260];
+        // the first request is lost
+        socket.recv_from(&mut buf).await.unwrap();
+        let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+        let Request::ReadHoldingRegisters(addr, nb) = Request::decode(&buf[7..len]).unwrap() else {
+            panic!("unexpected request");
+        };
+        let pdu = Response::ReadHoldingRegisters(vec![addr; nb.into()]).encode();
+
+        // late response of another transaction, then the response
+        let id = u16::from_be_bytes([buf[0], buf[1]]);
+        for id in [id.wrapping_sub(1), id] {
+            let mut frame = id.to_be_bytes().to_vec();
+            frame.extend([0, 0]);
+            frame.extend((pdu.len() as u16 + 1).to_be_bytes());
+            frame.push(buf[6]);
+            frame.extend(&pdu);
+            socket.send_to(&frame, peer).await.unwrap();
+        }
+    });
+
+    let mut ctx = UDPContext::new(addr);
+    ctx.retry_timeout = Duration::from_millis(50);
+    let mut device = ModbusDeviceAsync::new(ctx.into(), HashMap::new(), registers());
+    device.connect().await.unwrap();
+
+    let res = device.dump_holding_registers().await.unwrap();
+    assert_eq!(res["Level"], RegisterValue::U16(5));
+
+    // nobody answers anymore
+    assert!(device.dump_holding_registers().await.is_err());
+}
+
+// a write is sent once, its response may have been lost after it was applied
+#[tokio::test]
+async fn test_write_not_retransmitted() {
+    let socket = UdpSocket::bind(localhost()).await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    let received = tokio::spawn(async move {
+        let mut buf = [0u8; 260];
+        let mut requests = Vec::new();
+        while let Ok(Ok(len)) =
+            tokio::time::timeout(Duration::from_millis(200), socket.recv(&mut buf)).await
+        {
+            requests.push(Request::decode(&buf[7..len]).unwrap());
+        }
+        requests
+    });
+
+    let mut ctx = UDPContext::new(addr);
+    ctx.retry_timeout = Duration::from_millis(20);
+    let mut device = ModbusDeviceAsync::new(ctx.into(), HashMap::new(), registers());
+    device.connect().await.unwrap();
+
+    let res = device
+        .write_holding_register_by_name("Level", &RegisterValue::U16(42))
+        .await;
+    assert!(matches!(res, Err(ModbusError::Timeout)));
+    assert_eq!(
+        received.await.unwrap(),
+        [Request::WriteMultipleRegisters(5, vec![42])]
+    );
+}